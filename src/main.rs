@@ -1,6 +1,6 @@
 use bevy::prelude::*;
-use bevy_flycam::{MovementSettings, PlayerPlugin};
-use marching_cubes::plugins::chunks::ChunksPlugin;
+use bevy_flycam::{FlyCam, MovementSettings, PlayerPlugin};
+use marching_cubes::plugins::chunks::{components::ChunkLoader, ChunksPlugin};
 
 fn main() {
     App::new()
@@ -10,8 +10,9 @@ fn main() {
             speed: 100.,
             ..default()
         })
-        .add_plugin(ChunksPlugin)
+        .add_plugin(ChunksPlugin::default())
         .add_startup_system(setup)
+        .add_system(attach_chunk_loader)
         .run();
 }
 
@@ -27,3 +28,10 @@ fn setup(mut commands: Commands) {
         ..default()
     });
 }
+
+/// Stream chunks around the camera
+fn attach_chunk_loader(mut commands: Commands, cameras: Query<Entity, Added<FlyCam>>) {
+    for camera in cameras.iter() {
+        commands.entity(camera).insert(ChunkLoader::new(6));
+    }
+}
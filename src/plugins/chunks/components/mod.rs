@@ -1,5 +1,7 @@
 use super::resources::pos::Position;
+use bevy::prelude::Component;
 
+#[derive(Component)]
 pub struct ChunkComponent {
     pub pos: Position,
 }
@@ -9,3 +11,15 @@ impl ChunkComponent {
         Self { pos }
     }
 }
+
+/// Chunks within "radius" (in chunks) of entities with this component are kept loaded
+#[derive(Component)]
+pub struct ChunkLoader {
+    pub radius: i64,
+}
+
+impl ChunkLoader {
+    pub fn new(radius: i64) -> Self {
+        Self { radius }
+    }
+}
@@ -1,16 +1,115 @@
 use bevy::prelude::*;
+use std::{path::PathBuf, sync::Arc};
 
-use self::systems::{chunks_startup_sys, redraw_chunk::redraw_chunk_sys};
+use self::{
+    resources::{
+        generator::{ChunksGenerator, DefaultGenerator, DensityGenerator},
+        lod::LodSettings,
+        material::{terrain::TerrainMaterialPlugin, ChunkMaterial},
+        octree::OctreeSettings,
+        region::RegionStorage,
+        streaming::StreamingSettings,
+        tasks::{ChunkTasks, OctreeTasks, TasksSettings},
+    },
+    systems::{
+        chunks_startup_sys,
+        lod::update_lod_sys,
+        octree::{
+            apply_octree_meshes_sys, octree_startup_sys, redraw_octree_sys, update_octree_sys,
+        },
+        redraw_chunk::{apply_chunk_meshes_sys, redraw_chunk_sys},
+        save_chunks_on_exit_sys,
+        stream_chunks::{apply_generated_chunks_sys, stream_chunks_sys},
+    },
+};
 
 pub mod components;
 pub mod resources;
 mod systems;
 
-pub struct ChunksPlugin;
+pub struct ChunksPlugin {
+    generator: Arc<dyn DensityGenerator>,
+    save_dir: Option<PathBuf>,
+}
+
+impl ChunksPlugin {
+    pub fn new(generator: impl DensityGenerator + 'static) -> Self {
+        Self {
+            generator: Arc::new(generator),
+            save_dir: None,
+        }
+    }
+
+    /// Persist modified chunks in region files inside "dir", see [`RegionStorage`]
+    pub fn with_save_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.save_dir = Some(dir.into());
+        self
+    }
+}
+
+impl Default for ChunksPlugin {
+    fn default() -> Self {
+        Self::new(DefaultGenerator::default())
+    }
+}
 
 impl Plugin for ChunksPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(chunks_startup_sys)
-            .add_system(redraw_chunk_sys);
+        app.add_plugin(TerrainMaterialPlugin)
+            .insert_resource(ChunksGenerator(self.generator.clone()))
+            .init_resource::<StreamingSettings>()
+            .init_resource::<TasksSettings>()
+            .init_resource::<LodSettings>()
+            .init_resource::<ChunkTasks>()
+            .init_resource::<ChunkMaterial>()
+            .add_startup_system(chunks_startup_sys)
+            .add_system(stream_chunks_sys)
+            .add_system(apply_generated_chunks_sys.after(stream_chunks_sys))
+            .add_system(update_lod_sys.after(apply_generated_chunks_sys))
+            .add_system(redraw_chunk_sys.after(update_lod_sys))
+            .add_system(apply_chunk_meshes_sys.after(redraw_chunk_sys))
+            .add_system_to_stage(CoreStage::Last, save_chunks_on_exit_sys);
+
+        if let Some(dir) = &self.save_dir {
+            app.insert_resource(RegionStorage::new(dir.clone()));
+        }
+    }
+}
+
+/// Alternative to [`ChunksPlugin`] for large read-only worlds
+///
+/// Chunks are stored in [`ChunkOctree`] with coarser chunks far from loaders.
+///
+/// [`ChunkOctree`]: resources::octree::ChunkOctree
+pub struct OctreeChunksPlugin {
+    generator: Arc<dyn DensityGenerator>,
+}
+
+impl OctreeChunksPlugin {
+    pub fn new(generator: impl DensityGenerator + 'static) -> Self {
+        Self {
+            generator: Arc::new(generator),
+        }
+    }
+}
+
+impl Default for OctreeChunksPlugin {
+    fn default() -> Self {
+        Self::new(DefaultGenerator::default())
+    }
+}
+
+impl Plugin for OctreeChunksPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(TerrainMaterialPlugin)
+            .insert_resource(ChunksGenerator(self.generator.clone()))
+            .init_resource::<OctreeSettings>()
+            .init_resource::<TasksSettings>()
+            .init_resource::<OctreeTasks>()
+            .init_resource::<ChunkMaterial>()
+            .add_startup_system(octree_startup_sys)
+            .add_system(update_octree_sys)
+            .add_system(redraw_octree_sys.after(update_octree_sys))
+            .add_system(apply_octree_meshes_sys.after(redraw_octree_sys));
     }
 }
@@ -0,0 +1,184 @@
+use bevy::math::{Vec3, Vec3Swizzles};
+
+#[derive(Debug, Clone, Copy)]
+pub enum BrushShape {
+    Sphere {
+        radius: f32,
+    },
+    Cube {
+        half_size: f32,
+    },
+    /// Vertical cylinder
+    Cylinder {
+        radius: f32,
+        half_height: f32,
+    },
+}
+
+impl BrushShape {
+    /// Half size of the shape bounding box
+    pub fn extents(&self) -> Vec3 {
+        match *self {
+            Self::Sphere { radius } => Vec3::splat(radius),
+            Self::Cube { half_size } => Vec3::splat(half_size),
+            Self::Cylinder {
+                radius,
+                half_height,
+            } => Vec3::new(radius, half_height, radius),
+        }
+    }
+
+    /// All sizes are positive and finite, other shapes can't be applied
+    pub fn is_valid(&self) -> bool {
+        let extents = self.extents();
+        extents.is_finite() && extents.min_element() > 0.
+    }
+
+    /// Distance from the center scaled so that the shape border is at 1
+    fn normalized_distance(&self, offset: Vec3) -> f32 {
+        match *self {
+            Self::Sphere { radius } => offset.length() / radius,
+            Self::Cube { half_size } => offset.abs().max_element() / half_size,
+            Self::Cylinder {
+                radius,
+                half_height,
+            } => (offset.xz().length() / radius).max(offset.y.abs() / half_height),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum BrushOperation {
+    /// Increase density, filling the space
+    Add,
+    /// Decrease density, digging the space
+    Subtract,
+    /// Blend density with the average of neighbour voxels
+    Smooth,
+    /// Blend density towards a plane passing through the brush center,
+    /// "normal" points from the filled side to the empty one
+    Flatten { normal: Vec3 },
+    /// Set material of voxels, density is not changed
+    Paint { material: u8 },
+}
+
+/// Terrain editing tool, applied with [`ChunksHolder::apply_brush`]
+///
+/// [`ChunksHolder::apply_brush`]: super::ChunksHolder::apply_brush
+#[derive(Debug, Clone, Copy)]
+pub struct Brush {
+    pub shape: BrushShape,
+    pub operation: BrushOperation,
+    /// Max density change for add/subtract or blend factor for smooth/flatten
+    pub strength: f32,
+    /// Part of the brush radius (from 0 to 1) over which the strength fades out towards the border
+    pub falloff: f32,
+}
+
+impl Brush {
+    pub fn new(shape: BrushShape, operation: BrushOperation) -> Self {
+        Self {
+            shape,
+            operation,
+            strength: 1.,
+            falloff: 0.5,
+        }
+    }
+
+    pub fn with_strength(mut self, strength: f32) -> Self {
+        self.strength = strength;
+        self
+    }
+
+    pub fn with_falloff(mut self, falloff: f32) -> Self {
+        self.falloff = falloff;
+        self
+    }
+
+    /// Brush strength at "offset" from the brush center, zero for invalid shapes
+    pub fn get_weight(&self, offset: Vec3) -> f32 {
+        if !self.shape.is_valid() {
+            return 0.;
+        }
+
+        let distance = self.shape.normalized_distance(offset);
+        if distance > 1. {
+            return 0.;
+        }
+
+        let fade = if self.falloff > 0. {
+            ((1. - distance) / self.falloff).min(1.)
+        } else {
+            1.
+        };
+
+        self.strength * fade
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weight_fades_towards_border() {
+        let brush = Brush::new(BrushShape::Sphere { radius: 4. }, BrushOperation::Add)
+            .with_strength(2.)
+            .with_falloff(0.5);
+
+        // full strength in the inner half, linear fade in the outer one
+        assert_eq!(brush.get_weight(Vec3::ZERO), 2.);
+        assert_eq!(brush.get_weight(Vec3::new(0., 2., 0.)), 2.);
+        assert_eq!(brush.get_weight(Vec3::new(3., 0., 0.)), 1.);
+        assert_eq!(brush.get_weight(Vec3::new(0., 0., -4.)), 0.);
+        assert_eq!(brush.get_weight(Vec3::new(4.5, 0., 0.)), 0.);
+
+        let hard = brush.with_falloff(0.);
+        assert_eq!(hard.get_weight(Vec3::new(3.9, 0., 0.)), 2.);
+        assert_eq!(hard.get_weight(Vec3::new(4.1, 0., 0.)), 0.);
+    }
+
+    #[test]
+    fn shapes() {
+        let weight = |shape, offset| {
+            Brush::new(shape, BrushOperation::Add)
+                .with_falloff(0.)
+                .get_weight(offset)
+        };
+
+        let cube = BrushShape::Cube { half_size: 2. };
+        assert_eq!(weight(cube, Vec3::new(1.9, -1.9, 1.9)), 1.);
+        assert_eq!(weight(cube, Vec3::new(0., 2.1, 0.)), 0.);
+
+        let cylinder = BrushShape::Cylinder {
+            radius: 2.,
+            half_height: 5.,
+        };
+        assert_eq!(weight(cylinder, Vec3::new(1.4, 4.9, 1.4)), 1.);
+        assert_eq!(weight(cylinder, Vec3::new(1.5, 0., 1.5)), 0.);
+        assert_eq!(weight(cylinder, Vec3::new(0., -5.1, 0.)), 0.);
+
+        let sphere = BrushShape::Sphere { radius: 2. };
+        assert_eq!(weight(sphere, Vec3::new(1.4, 1.4, 0.)), 1.);
+        assert_eq!(weight(sphere, Vec3::new(1.2, 1.2, 1.2)), 0.);
+    }
+
+    #[test]
+    fn invalid_shapes_have_no_weight() {
+        for shape in [
+            BrushShape::Sphere { radius: 0. },
+            BrushShape::Cube { half_size: -1. },
+            BrushShape::Cylinder {
+                radius: 1.,
+                half_height: f32::NAN,
+            },
+            BrushShape::Sphere {
+                radius: f32::INFINITY,
+            },
+        ] {
+            assert!(!shape.is_valid());
+            let brush = Brush::new(shape, BrushOperation::Subtract);
+            assert_eq!(brush.get_weight(Vec3::ZERO), 0.);
+        }
+    }
+}
@@ -1,47 +1,111 @@
-use bevy::prelude::Mesh;
+use bevy::{
+    prelude::{Entity, Mesh},
+    tasks::TaskPool,
+};
+use std::sync::Arc;
 
 use super::{
-    mesh::{append_vertices::append_vertices, mesh_rom_vertices, Vertex},
+    chunk_view::{ChunkNeighbours, ChunkView},
+    generator::DensityGenerator,
+    lod::MAX_LOD,
+    mesh::{normals::NormalMode, MeshData, MeshSettings},
     pos::Position,
     voxel::Voxel,
+    voxel_storage::VoxelStorage,
 };
 
 pub const CHUNK_REAL_SIZE: usize = 32;
 pub const CHUNK_VOXELS_SIZE: usize = CHUNK_REAL_SIZE + 1;
 pub const CHUNK_VOLUME: usize = CHUNK_VOXELS_SIZE * CHUNK_VOXELS_SIZE * CHUNK_VOXELS_SIZE;
 
+/// Cloning is cheap, voxels are shared until one of the copies is modified
+#[derive(Clone)]
 pub struct Chunk {
     need_update: bool,
     pos: Position,
-    voxels: Box<Vec<Voxel>>,
+    voxels: Arc<VoxelStorage>,
+    /// entity with chunk's mesh, spawned on first redraw
+    entity: Option<Entity>,
+    /// overrides normal mode of the world
+    normal_mode: Option<NormalMode>,
+    /// level of detail, chunk is meshed with stride of 2^lod voxels
+    lod: u8,
+    /// voxels differ from generated ones
+    modified: bool,
+    /// voxels changed since the chunk was loaded or saved, only unsaved chunks are written
+    unsaved: bool,
+    /// min and max density, can be wider than actual range after voxels are modified
+    value_range: (f32, f32),
 }
 
 impl Chunk {
-    pub fn new(pos: Position) -> Self {
+    pub fn new(pos: Position, generator: &dyn DensityGenerator) -> Self {
         // generate chunk's voxels
-        let voxels: Box<Vec<Voxel>> = Box::new(
-            (0..CHUNK_VOLUME)
-                .map(|index| {
-                    // voxel world position - chunk offset plus voxel inchunk pos
-                    let pos = Self::get_pos_by_index(index) + pos * (CHUNK_REAL_SIZE as i64);
-
-                    let scale = 5.;
-                    let stretch = 10.;
-                    let value = pos.y as f32
-                        + ((pos.x as f32 / stretch).cos() + (pos.z as f32 / stretch).sin()) / 2.
-                            * scale;
-                    Voxel { value }
-                })
-                .collect(),
-        );
+        let voxels: Vec<Voxel> = (0..CHUNK_VOLUME)
+            .map(|index| Self::generate_voxel(pos, index, generator))
+            .collect();
+
+        Self::from_voxels(pos, voxels)
+    }
+
+    /// Same as [`Chunk::new`], but each slice of voxels along Z axis is generated in parallel
+    ///
+    /// Used for chunks generated on the main thread, streamed chunks are already generated in
+    /// parallel tasks with [`Chunk::new`].
+    pub fn new_parallel(pos: Position, generator: &dyn DensityGenerator, pool: &TaskPool) -> Self {
+        let slice_volume = CHUNK_VOXELS_SIZE * CHUNK_VOXELS_SIZE;
+
+        // scope returns results in the same order as tasks were spawned
+        let slices: Vec<Vec<Voxel>> = pool.scope(|scope| {
+            for slice in 0..CHUNK_VOXELS_SIZE {
+                scope.spawn(async move {
+                    (slice * slice_volume..(slice + 1) * slice_volume)
+                        .map(|index| Self::generate_voxel(pos, index, generator))
+                        .collect()
+                });
+            }
+        });
+
+        Self::from_voxels(pos, slices.concat())
+    }
+
+    fn from_voxels(pos: Position, voxels: Vec<Voxel>) -> Self {
+        let voxels = VoxelStorage::new(voxels.into_iter().map(Voxel::clamped).collect());
 
         Self {
-            voxels,
+            value_range: voxels.get_value_range(),
+            voxels: Arc::new(voxels),
             need_update: true,
             pos,
+            entity: None,
+            normal_mode: None,
+            lod: 0,
+            modified: false,
+            unsaved: false,
+        }
+    }
+
+    /// Chunk with voxels loaded from [`RegionStorage`]
+    ///
+    /// [`RegionStorage`]: super::region::RegionStorage
+    pub(super) fn from_saved(pos: Position, voxels: Vec<Voxel>) -> Self {
+        Self {
+            modified: true,
+            ..Self::from_voxels(pos, voxels)
         }
     }
 
+    fn generate_voxel(
+        chunk_pos: Position,
+        index: usize,
+        generator: &dyn DensityGenerator,
+    ) -> Voxel {
+        // voxel world position - chunk offset plus voxel inchunk pos
+        let pos = Self::get_pos_by_index(index) + chunk_pos * (CHUNK_REAL_SIZE as i64);
+
+        generator.get_voxel(pos)
+    }
+
     fn get_pos_by_index(index: usize) -> Position {
         Position::new(
             (index % CHUNK_VOXELS_SIZE) as i64,
@@ -60,6 +124,14 @@ impl Chunk {
         self.pos
     }
 
+    pub fn get_entity(&self) -> Option<Entity> {
+        self.entity
+    }
+
+    pub fn set_entity(&mut self, entity: Option<Entity>) {
+        self.entity = entity;
+    }
+
     pub fn is_need_update(&self) -> bool {
         self.need_update
     }
@@ -68,33 +140,147 @@ impl Chunk {
         self.need_update = false;
     }
 
-    pub fn get_voxel(&self, pos: Position) -> Voxel {
-        self.voxels[Self::get_index_by_pos(pos)]
-    }
-
-    pub fn generate_vertices(&self) -> Vec<Vertex> {
-        let mut vertices: Vec<Vertex> = Vec::new();
-        for x in 0..CHUNK_REAL_SIZE {
-            for y in 0..CHUNK_REAL_SIZE {
-                for z in 0..CHUNK_REAL_SIZE {
-                    append_vertices(
-                        Position::new(x as i64, y as i64, z as i64),
-                        self,
-                        &mut vertices,
-                    );
-                }
-            }
+    pub fn set_need_update(&mut self) {
+        self.need_update = true;
+    }
+
+    pub fn get_normal_mode(&self) -> Option<NormalMode> {
+        self.normal_mode
+    }
+
+    /// Override normal mode from [`MeshSettings`] for this chunk, "None" resets it
+    pub fn set_normal_mode(&mut self, normal_mode: Option<NormalMode>) {
+        self.normal_mode = normal_mode;
+        self.need_update = true;
+    }
+
+    pub fn get_lod(&self) -> u8 {
+        self.lod
+    }
+
+    /// Set level of detail and mark chunk for redraw if it changed
+    pub fn set_lod(&mut self, lod: u8) {
+        let lod = lod.min(MAX_LOD);
+        if self.lod != lod {
+            self.lod = lod;
+            self.need_update = true;
         }
+    }
 
-        for v in vertices.iter_mut() {
-            v.pos = v.pos + (self.pos * (CHUNK_REAL_SIZE as i64)).to_vec();
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    pub fn is_unsaved(&self) -> bool {
+        self.unsaved
+    }
+
+    /// Mark voxels as written to [`RegionStorage`]
+    ///
+    /// [`RegionStorage`]: super::region::RegionStorage
+    pub(super) fn set_saved(&mut self) {
+        self.unsaved = false;
+    }
+
+    /// Unpacked voxels ordered by x, then y, then z
+    pub(super) fn get_voxels(&self) -> Vec<Voxel> {
+        self.voxels.to_vec()
+    }
+
+    pub fn get_storage(&self) -> &VoxelStorage {
+        &self.voxels
+    }
+
+    /// Pack voxels which were unpacked by [`Chunk::set_voxel`]
+    pub fn compress(&mut self) {
+        if matches!(self.voxels.as_ref(), VoxelStorage::Dense(_)) {
+            Arc::make_mut(&mut self.voxels).compress();
+            self.value_range = self.voxels.get_value_range();
         }
+    }
+
+    /// All voxels are on the same side of the surface at "iso_level", so chunk has no mesh
+    ///
+    /// Meshers only emit geometry for edges between chunk voxels, so apron voxels don't matter.
+    pub fn is_uniform(&self, iso_level: f32) -> bool {
+        let (min, max) = self.value_range;
+        min >= iso_level || max < iso_level
+    }
+
+    pub fn get_voxel(&self, pos: Position) -> Voxel {
+        self.voxels.get(Self::get_index_by_pos(pos))
+    }
+
+    /// Set voxel at in-chunk position "pos" and mark chunk for redraw
+    ///
+    /// Density is clamped to [`DENSITY_LIMIT`].
+    ///
+    /// [`DENSITY_LIMIT`]: super::voxel::DENSITY_LIMIT
+    pub fn set_voxel(&mut self, pos: Position, voxel: Voxel) {
+        let voxel = voxel.clamped();
+        Arc::make_mut(&mut self.voxels).set(Self::get_index_by_pos(pos), voxel);
 
-        vertices
+        let (min, max) = self.value_range;
+        self.value_range = (min.min(voxel.value), max.max(voxel.value));
+        self.need_update = true;
+        self.modified = true;
+        self.unsaved = true;
     }
 
-    pub fn generate_mesh(&self) -> Mesh {
-        let vertices = self.generate_vertices();
-        mesh_rom_vertices(vertices)
+    /// Generate vertices in world coordinates
+    ///
+    /// Voxels outside of the chunk are taken from "generator", use [`ChunkView`] built from
+    /// loaded neighbours to take modified voxels into account.
+    pub fn generate_vertices(
+        &self,
+        settings: &MeshSettings,
+        generator: &dyn DensityGenerator,
+    ) -> MeshData {
+        self.get_view(generator).generate_vertices(settings)
+    }
+
+    pub fn generate_mesh(&self, settings: &MeshSettings, generator: &dyn DensityGenerator) -> Mesh {
+        self.get_view(generator).generate_mesh(settings)
+    }
+
+    fn get_view(&self, generator: &dyn DensityGenerator) -> ChunkView {
+        let neighbours =
+            ChunkNeighbours::new(self.pos, |pos| (pos == self.pos).then(|| self.clone()));
+        ChunkView::new(&neighbours, generator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::chunks::resources::generator::DefaultGenerator;
+
+    #[test]
+    fn new_parallel_matches_new() {
+        let generator = DefaultGenerator::default();
+        let pool = TaskPool::new();
+
+        for pos in [Position::new(0, 0, 0), Position::new(-3, 1, 2)] {
+            let serial = Chunk::new(pos, &generator);
+            let parallel = Chunk::new_parallel(pos, &generator, &pool);
+
+            let (serial, parallel) = (serial.get_voxels(), parallel.get_voxels());
+            assert_eq!(serial.len(), parallel.len());
+            for (a, b) in serial.iter().zip(parallel.iter()) {
+                assert_eq!(a.value.to_bits(), b.value.to_bits());
+                assert_eq!(a.material, b.material);
+            }
+        }
+    }
+
+    #[test]
+    fn set_lod_above_max_is_stable() {
+        let mut chunk = Chunk::new(Position::new(0, 0, 0), &DefaultGenerator::default());
+        chunk.set_lod(MAX_LOD + 2);
+        chunk.set_updated();
+
+        chunk.set_lod(MAX_LOD + 2);
+        assert_eq!(chunk.get_lod(), MAX_LOD);
+        assert!(!chunk.is_need_update());
     }
 }
@@ -0,0 +1,334 @@
+use super::{
+    chunk::{Chunk, CHUNK_REAL_SIZE, CHUNK_VOXELS_SIZE},
+    generator::DensityGenerator,
+    mesh::{
+        mesh_from_data,
+        normals::{get_gradient, into_flat, NormalMode},
+        skirts::{append_skirts, SkirtFace},
+        MeshData, MeshSettings,
+    },
+    pos::Position,
+    voxel::Voxel,
+};
+use bevy::prelude::Mesh;
+
+/// Min number of voxels taken from neighbour chunks at each side of the view,
+/// see [`ChunkView::get_apron`]
+pub const CHUNK_VIEW_APRON: usize = 2;
+
+/// Chunk with its 26 neighbours, cheap to clone and send to other threads
+#[derive(Clone)]
+pub struct ChunkNeighbours {
+    pos: Position,
+    /// indexed by (x + 1) + (y + 1) * 3 + (z + 1) * 9 of neighbour offset
+    chunks: Vec<Option<Chunk>>,
+}
+
+impl ChunkNeighbours {
+    pub fn new(pos: Position, get_chunk: impl Fn(Position) -> Option<Chunk>) -> Self {
+        let chunks = (0..27)
+            .map(|index| get_chunk(pos + Self::get_offset_by_index(index)))
+            .collect();
+
+        Self { pos, chunks }
+    }
+
+    fn get_offset_by_index(index: usize) -> Position {
+        Position::new(
+            (index % 3) as i64 - 1,
+            ((index / 3) % 3) as i64 - 1,
+            (index / 9) as i64 - 1,
+        )
+    }
+
+    pub fn get(&self, offset: Position) -> Option<&Chunk> {
+        let index = (offset.x + 1) + (offset.y + 1) * 3 + (offset.z + 1) * 9;
+        self.chunks[index as usize].as_ref()
+    }
+
+    pub fn get_center(&self) -> Option<&Chunk> {
+        self.get(Position::new(0, 0, 0))
+    }
+}
+
+/// Chunk voxels padded with an apron of voxels from neighbour chunks
+///
+/// In-chunk positions from -apron to [`CHUNK_REAL_SIZE`] + apron can be accessed, so meshing can read past chunk borders and produce matching geometry and
+/// normals on both sides of the seam.
+pub struct ChunkView {
+    pos: Position,
+    voxels: Vec<Voxel>,
+    apron: usize,
+    /// number of voxels along each axis
+    size: usize,
+    normal_mode: Option<NormalMode>,
+    lod: u8,
+    /// faces which need skirts due to different level of detail of the neighbour
+    skirts: Vec<SkirtFace>,
+}
+
+impl ChunkView {
+    /// Voxels of missing neighbour chunks are taken from "generator"
+    pub fn new(neighbours: &ChunkNeighbours, generator: &dyn DensityGenerator) -> Self {
+        let pos = neighbours.pos;
+        let center = neighbours.get_center();
+        let lod = center.map(|chunk| chunk.get_lod()).unwrap_or(0);
+        let view_apron = Self::get_apron(lod);
+        let view_size = CHUNK_VOXELS_SIZE + view_apron * 2;
+
+        let apron = view_apron as i64;
+        let size = CHUNK_REAL_SIZE as i64;
+
+        // neighbour offset and in-neighbour coordinate along single axis
+        let split = |local: i64| {
+            if local < 0 {
+                (-1, local + size)
+            } else if local > size {
+                (1, local - size)
+            } else {
+                (0, local)
+            }
+        };
+
+        let mut voxels = Vec::with_capacity(view_size * view_size * view_size);
+        for z in -apron..=size + apron {
+            for y in -apron..=size + apron {
+                for x in -apron..=size + apron {
+                    let (offset_x, local_x) = split(x);
+                    let (offset_y, local_y) = split(y);
+                    let (offset_z, local_z) = split(z);
+                    let offset = Position::new(offset_x, offset_y, offset_z);
+
+                    let voxel = match neighbours.get(offset) {
+                        Some(chunk) => chunk.get_voxel(Position::new(local_x, local_y, local_z)),
+                        // clamped the same way as voxels stored in chunks
+                        None => generator
+                            .get_voxel(Position::new(x, y, z) + pos * size)
+                            .clamped(),
+                    };
+                    voxels.push(voxel);
+                }
+            }
+        }
+
+        Self {
+            pos,
+            voxels,
+            apron: view_apron,
+            size: view_size,
+            normal_mode: center.and_then(|chunk| chunk.get_normal_mode()),
+            lod,
+            skirts: Self::get_skirt_faces(neighbours, lod),
+        }
+    }
+
+    /// Apron of view for chunk at level of detail "lod"
+    ///
+    /// Dual meshers read voxels of cells one stride before the chunk and gradient around them.
+    pub fn get_apron(lod: u8) -> usize {
+        CHUNK_VIEW_APRON.max((1 << lod) + 1)
+    }
+
+    /// Faces shared with loaded neighbours meshed at different level of detail
+    fn get_skirt_faces(neighbours: &ChunkNeighbours, lod: u8) -> Vec<SkirtFace> {
+        let mut faces = Vec::new();
+        for axis in 0..3 {
+            for side in [-1, 1] {
+                let mut offset = [0; 3];
+                offset[axis] = side;
+
+                let offset = Position::new(offset[0], offset[1], offset[2]);
+                let neighbour_lod = match neighbours.get(offset) {
+                    Some(chunk) if chunk.get_lod() != lod => chunk.get_lod(),
+                    _ => continue,
+                };
+
+                faces.push(SkirtFace {
+                    axis,
+                    coordinate: if side < 0 { 0. } else { CHUNK_REAL_SIZE as f32 },
+                    depth: (1 << lod.max(neighbour_lod)) as f32,
+                });
+            }
+        }
+
+        faces
+    }
+
+    /// Replace faces which get skirts, used when neighbours are not stored in a [`ChunksHolder`]
+    ///
+    /// [`ChunksHolder`]: super::ChunksHolder
+    pub fn with_skirt_faces(mut self, faces: Vec<SkirtFace>) -> Self {
+        self.skirts = faces;
+        self
+    }
+
+    pub fn get_pos(&self) -> Position {
+        self.pos
+    }
+
+    /// Get voxel at in-chunk position "pos", which can be outside of the chunk by apron size,
+    /// see [`ChunkView::get_apron`]
+    pub fn get_voxel(&self, pos: Position) -> Voxel {
+        let apron = self.apron as i64;
+        let index = (pos.x + apron) as usize
+            + (pos.y + apron) as usize * self.size
+            + (pos.z + apron) as usize * self.size * self.size;
+
+        self.voxels[index]
+    }
+
+    /// Generate vertices in world coordinates
+    pub fn generate_vertices(&self, settings: &MeshSettings) -> MeshData {
+        let mut data = MeshData::default();
+        let mesher = settings.mesher.get_mesher();
+        let stride = 1 << self.lod;
+        mesher.generate(self, stride, settings.iso_level, &mut data);
+
+        let normal_mode = self.normal_mode.unwrap_or(settings.normal_mode);
+        if normal_mode == NormalMode::Gradient {
+            let sample = |pos: Position| self.get_voxel(pos).value;
+            for v in data.vertices.iter_mut() {
+                // triangle normals point towards lower density
+                v.normal = -get_gradient(&sample, v.pos).normalize_or_zero();
+            }
+        } else {
+            // normals are accumulated from all adjacent triangles
+            for v in data.vertices.iter_mut() {
+                v.normal = v.normal.normalize_or_zero();
+            }
+        }
+
+        // skirts need shared vertices to find mesh borders
+        append_skirts(&mut data, &self.skirts, mesher.get_border_margin(stride));
+
+        if normal_mode == NormalMode::Flat {
+            data = into_flat(data);
+        }
+
+        let offset = (self.pos * (CHUNK_REAL_SIZE as i64)).to_vec();
+        for v in data.vertices.iter_mut() {
+            v.pos += offset;
+        }
+
+        data
+    }
+
+    pub fn generate_mesh(&self, settings: &MeshSettings) -> Mesh {
+        mesh_from_data(self.generate_vertices(settings))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::{
+        math::{Vec2, Vec3Swizzles},
+        utils::HashMap,
+    };
+
+    /// Hills varying along both axes of the face between chunks at x = 0 and x = 1
+    struct Hills;
+
+    impl DensityGenerator for Hills {
+        fn get_voxel(&self, pos: Position) -> Voxel {
+            let (x, z) = (pos.x as f32, pos.z as f32);
+            let height = 16. + 4. * (z * 0.35).sin() + 2. * (x * 0.3 + z * 0.2).sin();
+            Voxel {
+                value: height - pos.y as f32,
+                material: 0,
+            }
+        }
+    }
+
+    /// Segments in (z, y) of surface border edges lying on the plane x = "face"
+    fn get_border(data: &MeshData, face: f32) -> Vec<(Vec2, Vec2)> {
+        let (vertices, indices) = data.get_surface();
+        let mut edges: HashMap<(u32, u32), u32> = HashMap::default();
+        for triangle in indices.chunks_exact(3) {
+            for i in 0..3 {
+                let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+                *edges.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+            }
+        }
+
+        edges
+            .into_iter()
+            .filter(|&(_, count)| count == 1)
+            .map(|((a, b), _)| (vertices[a as usize].pos, vertices[b as usize].pos))
+            .filter(|(a, b)| (a.x - face).abs() < 1e-3 && (b.x - face).abs() < 1e-3)
+            .map(|(a, b)| (a.zy(), b.zy()))
+            .collect()
+    }
+
+    /// Height of the border at "z"
+    fn get_border_height(border: &[(Vec2, Vec2)], z: f32) -> f32 {
+        border
+            .iter()
+            .find_map(|&(a, b)| {
+                let t = (z - a.x) / (b.x - a.x);
+                (0. ..=1.).contains(&t).then(|| a.y + (b.y - a.y) * t)
+            })
+            .expect("border spans the face")
+    }
+
+    /// Skirt triangles projected on the face plane contain point "p" in (z, y)
+    fn is_covered(data: &MeshData, p: Vec2) -> bool {
+        let (_, start) = data.skirts_start.expect("chunk has skirts");
+        data.indices[start..].chunks_exact(3).any(|triangle| {
+            let [a, b, c] = [0, 1, 2].map(|i| data.vertices[triangle[i] as usize].pos.zy());
+            let side = |from: Vec2, to: Vec2| (to - from).perp_dot(p - from);
+            let sides = [side(a, b), side(b, c), side(c, a)];
+            sides.iter().all(|&side| side >= -1e-4) || sides.iter().all(|&side| side <= 1e-4)
+        })
+    }
+
+    #[test]
+    fn skirts_cover_border_between_levels_of_detail() {
+        let mut fine = Chunk::new(Position::new(0, 0, 0), &Hills);
+        let mut coarse = Chunk::new(Position::new(1, 0, 0), &Hills);
+        fine.set_lod(0);
+        coarse.set_lod(1);
+
+        let chunks: HashMap<Position, Chunk> = [fine, coarse]
+            .into_iter()
+            .map(|chunk| (chunk.get_pos(), chunk))
+            .collect();
+        let meshes: Vec<MeshData> = chunks
+            .keys()
+            .map(|&pos| {
+                let neighbours = ChunkNeighbours::new(pos, |pos| chunks.get(&pos).cloned());
+                ChunkView::new(&neighbours, &Hills).generate_vertices(&MeshSettings::default())
+            })
+            .collect();
+
+        let face = CHUNK_REAL_SIZE as f32;
+        let borders: Vec<_> = meshes.iter().map(|data| get_border(data, face)).collect();
+
+        let mut max_gap: f32 = 0.;
+        for step in 1..80 {
+            // chunk corners have borders along other faces too
+            let z = step as f32 * 0.39 + 0.5;
+            let (a, b) = (
+                get_border_height(&borders[0], z),
+                get_border_height(&borders[1], z),
+            );
+            max_gap = max_gap.max((a - b).abs());
+
+            // any point of the crack between borders is hidden by one of the skirts
+            for t in 1..10 {
+                let p = Vec2::new(z, a + (b - a) * t as f32 / 10.);
+                assert!(
+                    meshes.iter().any(|data| is_covered(data, p)),
+                    "crack at {:?} between {} and {}",
+                    p,
+                    a,
+                    b
+                );
+            }
+        }
+        assert!(
+            max_gap > 0.05,
+            "borders match, so there is nothing to cover"
+        );
+    }
+}
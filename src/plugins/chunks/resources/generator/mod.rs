@@ -0,0 +1,127 @@
+use super::{
+    pos::Position,
+    voxel::{materials, Voxel},
+};
+use std::sync::Arc;
+
+pub mod noise;
+pub mod sdf;
+
+/// Source of voxel data for newly created chunks
+///
+/// Density grows into the ground: voxels at or above the iso level are filled, open space has
+/// lower density.
+///
+/// Implement this trait to provide custom terrain and pass it to [`ChunksPlugin::new`]
+///
+/// [`ChunksPlugin::new`]: crate::plugins::chunks::ChunksPlugin::new
+pub trait DensityGenerator: Send + Sync {
+    /// Sample voxel at world position "pos"
+    ///
+    /// Chunks store densities clamped to [`DENSITY_LIMIT`], so values beyond it are never seen
+    /// outside of the generator.
+    ///
+    /// [`DENSITY_LIMIT`]: super::voxel::DENSITY_LIMIT
+    fn get_voxel(&self, pos: Position) -> Voxel;
+}
+
+/// Default terrain: smooth hills made from cos/sin waves
+pub struct DefaultGenerator {
+    pub scale: f32,
+    pub stretch: f32,
+}
+
+impl Default for DefaultGenerator {
+    fn default() -> Self {
+        Self {
+            scale: 5.,
+            stretch: 10.,
+        }
+    }
+}
+
+impl DensityGenerator for DefaultGenerator {
+    fn get_voxel(&self, pos: Position) -> Voxel {
+        // ground below the surface is filled
+        let value = -pos.y as f32
+            - ((pos.x as f32 / self.stretch).cos() + (pos.z as f32 / self.stretch).sin()) / 2.
+                * self.scale;
+        Voxel {
+            value,
+            material: materials::DIRT,
+        }
+    }
+}
+
+/// Samples "generator" every "scale" voxels, used for coarse chunks of [`ChunkOctree`]
+///
+/// Density is divided by "scale" to stay in units of the scaled voxels.
+///
+/// [`ChunkOctree`]: crate::plugins::chunks::resources::octree::ChunkOctree
+#[derive(Clone)]
+pub struct ScaledGenerator {
+    generator: Arc<dyn DensityGenerator>,
+    scale: i64,
+}
+
+impl ScaledGenerator {
+    pub fn new(generator: Arc<dyn DensityGenerator>, scale: i64) -> Self {
+        Self { generator, scale }
+    }
+}
+
+impl DensityGenerator for ScaledGenerator {
+    fn get_voxel(&self, pos: Position) -> Voxel {
+        let voxel = self.generator.get_voxel(pos * self.scale);
+        Voxel {
+            value: voxel.value / self.scale as f32,
+            ..voxel
+        }
+    }
+}
+
+/// Assigns materials to voxels of "generator" by world height
+pub struct MaterialLayers<G: DensityGenerator> {
+    pub generator: G,
+    /// lowest height and material of each layer, sorted by height
+    layers: Vec<(i64, u8)>,
+}
+
+impl<G: DensityGenerator> MaterialLayers<G> {
+    /// All voxels get "material" until layers above are added
+    pub fn new(generator: G, material: u8) -> Self {
+        Self {
+            generator,
+            layers: vec![(i64::MIN, material)],
+        }
+    }
+
+    /// Voxels at "height" and above get "material"
+    pub fn with_layer(mut self, height: i64, material: u8) -> Self {
+        self.layers.push((height, material));
+        self.layers.sort_by_key(|&(height, _)| height);
+        self
+    }
+}
+
+impl<G: DensityGenerator> DensityGenerator for MaterialLayers<G> {
+    fn get_voxel(&self, pos: Position) -> Voxel {
+        let material = self
+            .layers
+            .iter()
+            .rev()
+            .find(|&&(height, _)| pos.y >= height)
+            .map_or(materials::DIRT, |&(_, material)| material);
+
+        Voxel {
+            material,
+            ..self.generator.get_voxel(pos)
+        }
+    }
+}
+
+/// Generator used to fill chunks of the world, inserted by [`ChunksPlugin`]
+///
+/// [`ChunksPlugin`]: crate::plugins::chunks::ChunksPlugin
+#[derive(Clone)]
+pub struct ChunksGenerator(pub Arc<dyn DensityGenerator>);
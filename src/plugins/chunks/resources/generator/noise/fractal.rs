@@ -0,0 +1,171 @@
+use super::NoiseFn;
+use bevy::math::{const_vec3, Vec3};
+
+/// Offset between octaves, so they don't share the lattice origin
+const OCTAVE_OFFSET: Vec3 = const_vec3!([31.416, -17.32, 5.774]);
+
+/// Fractal parameters shared by all combinators
+#[derive(Debug, Clone, Copy)]
+pub struct Octaves {
+    pub count: u32,
+    pub lacunarity: f32,
+    pub persistence: f32,
+}
+
+impl Default for Octaves {
+    fn default() -> Self {
+        Self {
+            count: 5,
+            lacunarity: 2.,
+            persistence: 0.5,
+        }
+    }
+}
+
+impl Octaves {
+    /// Call "f" with (point, amplitude) for each octave and return sum of amplitudes
+    fn for_each(&self, p: Vec3, mut f: impl FnMut(Vec3, f32)) -> f32 {
+        let mut frequency = 1.;
+        let mut amplitude = 1.;
+        let mut total_amplitude = 0.;
+
+        for octave in 0..self.count {
+            f(p * frequency + OCTAVE_OFFSET * octave as f32, amplitude);
+
+            total_amplitude += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.persistence;
+        }
+
+        total_amplitude
+    }
+}
+
+/// Fractal Brownian motion: sum of octaves with decreasing amplitude
+pub struct Fbm<N: NoiseFn> {
+    pub source: N,
+    pub octaves: Octaves,
+}
+
+impl<N: NoiseFn> Fbm<N> {
+    pub fn new(source: N) -> Self {
+        Self {
+            source,
+            octaves: Octaves::default(),
+        }
+    }
+
+    pub fn with_octaves(mut self, octaves: Octaves) -> Self {
+        self.octaves = octaves;
+        self
+    }
+}
+
+impl<N: NoiseFn> NoiseFn for Fbm<N> {
+    fn get(&self, p: Vec3) -> f32 {
+        let mut sum = 0.;
+        let total = self
+            .octaves
+            .for_each(p, |p, amplitude| sum += self.source.get(p) * amplitude);
+
+        sum / total
+    }
+}
+
+/// Billow noise: fBm of absolute values, gives puffy rounded shapes
+pub struct Billow<N: NoiseFn> {
+    pub source: N,
+    pub octaves: Octaves,
+}
+
+impl<N: NoiseFn> Billow<N> {
+    pub fn new(source: N) -> Self {
+        Self {
+            source,
+            octaves: Octaves::default(),
+        }
+    }
+
+    pub fn with_octaves(mut self, octaves: Octaves) -> Self {
+        self.octaves = octaves;
+        self
+    }
+}
+
+impl<N: NoiseFn> NoiseFn for Billow<N> {
+    fn get(&self, p: Vec3) -> f32 {
+        let mut sum = 0.;
+        let total = self.octaves.for_each(p, |p, amplitude| {
+            sum += (self.source.get(p).abs() * 2. - 1.) * amplitude
+        });
+
+        sum / total
+    }
+}
+
+/// Ridged multifractal: sharp ridges, each octave is weighted by the previous one
+pub struct RidgedMulti<N: NoiseFn> {
+    pub source: N,
+    pub octaves: Octaves,
+    pub gain: f32,
+}
+
+impl<N: NoiseFn> RidgedMulti<N> {
+    pub fn new(source: N) -> Self {
+        Self {
+            source,
+            octaves: Octaves::default(),
+            gain: 2.,
+        }
+    }
+
+    pub fn with_octaves(mut self, octaves: Octaves) -> Self {
+        self.octaves = octaves;
+        self
+    }
+}
+
+impl<N: NoiseFn> NoiseFn for RidgedMulti<N> {
+    fn get(&self, p: Vec3) -> f32 {
+        let mut sum = 0.;
+        let mut weight = 1.;
+        let total = self.octaves.for_each(p, |p, amplitude| {
+            let signal = 1. - self.source.get(p).abs();
+            let signal = signal * signal * weight;
+
+            weight = (signal * self.gain).clamp(0., 1.);
+            sum += signal * amplitude;
+        });
+
+        sum / total * 2. - 1.
+    }
+}
+
+/// Domain warping: offset sample point of "source" by values of "warp"
+pub struct DomainWarp<N: NoiseFn, W: NoiseFn> {
+    pub source: N,
+    pub warp: W,
+    pub strength: f32,
+}
+
+impl<N: NoiseFn, W: NoiseFn> DomainWarp<N, W> {
+    pub fn new(source: N, warp: W, strength: f32) -> Self {
+        Self {
+            source,
+            warp,
+            strength,
+        }
+    }
+}
+
+impl<N: NoiseFn, W: NoiseFn> NoiseFn for DomainWarp<N, W> {
+    fn get(&self, p: Vec3) -> f32 {
+        let offset = Vec3::new(
+            self.warp.get(p),
+            self.warp.get(p + Vec3::new(5.2, 1.3, 7.1)),
+            self.warp.get(p + Vec3::new(1.7, 9.2, 3.4)),
+        );
+
+        self.source.get(p + offset * self.strength)
+    }
+}
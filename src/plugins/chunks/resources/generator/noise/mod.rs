@@ -0,0 +1,236 @@
+use super::DensityGenerator;
+use crate::plugins::chunks::resources::{
+    pos::Position,
+    voxel::{materials, Voxel},
+};
+use bevy::math::Vec3;
+
+pub mod fractal;
+pub mod open_simplex;
+pub mod perlin;
+pub mod worley;
+
+/// Deterministic 3D noise function
+///
+/// All implementations use only integer hashing and basic float arithmetic,
+/// so the same seed produces the same values on every run and machine.
+pub trait NoiseFn: Send + Sync {
+    /// Sample noise at point "p", result is roughly in range [-1, 1]
+    fn get(&self, p: Vec3) -> f32;
+}
+
+impl NoiseFn for Box<dyn NoiseFn> {
+    fn get(&self, p: Vec3) -> f32 {
+        self.as_ref().get(p)
+    }
+}
+
+/// Hash integer lattice point with seed (splitmix64 finalizer)
+pub(crate) fn hash(seed: u64, x: i32, y: i32, z: i32) -> u64 {
+    let mut h = seed
+        ^ (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ (z as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+    h = (h ^ (h >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    h ^ (h >> 31)
+}
+
+/// Map hash to float in range [0, 1)
+pub(crate) fn hash_to_unit(h: u64) -> f32 {
+    (h >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// One of 12 cube edge directions, used as gradient vector
+pub(crate) fn gradient(h: u64) -> Vec3 {
+    match h % 12 {
+        0 => Vec3::new(1., 1., 0.),
+        1 => Vec3::new(-1., 1., 0.),
+        2 => Vec3::new(1., -1., 0.),
+        3 => Vec3::new(-1., -1., 0.),
+        4 => Vec3::new(1., 0., 1.),
+        5 => Vec3::new(-1., 0., 1.),
+        6 => Vec3::new(1., 0., -1.),
+        7 => Vec3::new(-1., 0., -1.),
+        8 => Vec3::new(0., 1., 1.),
+        9 => Vec3::new(0., -1., 1.),
+        10 => Vec3::new(0., 1., -1.),
+        _ => Vec3::new(0., -1., -1.),
+    }
+}
+
+/// Terrain generator that displaces the ground level by noise
+///
+/// # Example
+/// ```no_run
+/// use marching_cubes::plugins::chunks::{
+///     resources::generator::noise::{fractal::Fbm, perlin::Perlin, NoiseGenerator},
+///     ChunksPlugin,
+/// };
+///
+/// let generator = NoiseGenerator::new(Fbm::new(Perlin::new(42)), 0.01, 40.);
+/// let plugin = ChunksPlugin::new(generator);
+/// ```
+pub struct NoiseGenerator<N: NoiseFn> {
+    pub noise: N,
+    pub frequency: f32,
+    pub amplitude: f32,
+    /// sample noise in 3D to get overhangs and caves instead of a heightmap
+    pub volumetric: bool,
+}
+
+impl<N: NoiseFn> NoiseGenerator<N> {
+    pub fn new(noise: N, frequency: f32, amplitude: f32) -> Self {
+        Self {
+            noise,
+            frequency,
+            amplitude,
+            volumetric: false,
+        }
+    }
+
+    pub fn volumetric(mut self) -> Self {
+        self.volumetric = true;
+        self
+    }
+}
+
+impl<N: NoiseFn> DensityGenerator for NoiseGenerator<N> {
+    fn get_voxel(&self, pos: Position) -> Voxel {
+        let mut sample = pos.to_vec() * self.frequency;
+        if !self.volumetric {
+            sample.y = 0.;
+        }
+
+        Voxel {
+            value: -pos.y as f32 - self.noise.get(sample) * self.amplitude,
+            material: materials::DIRT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        fractal::{Billow, DomainWarp, Fbm, RidgedMulti},
+        open_simplex::OpenSimplex,
+        perlin::Perlin,
+        worley::{Worley, WorleyFeature},
+        *,
+    };
+    use bevy::math::const_vec3;
+
+    const POINTS: [Vec3; 2] = [
+        const_vec3!([0.3, 1.7, -2.4]),
+        const_vec3!([-12.6, 5.1, 0.45]),
+    ];
+
+    /// Noise functions with seed "seed" and their expected values at [`POINTS`] for seed 1
+    fn get_noises(seed: u64) -> Vec<(&'static str, Box<dyn NoiseFn>, [f32; 2])> {
+        vec![
+            (
+                "perlin",
+                Box::new(Perlin::new(seed)),
+                [-0.04101503, 0.24843937],
+            ),
+            (
+                "open simplex",
+                Box::new(OpenSimplex::new(seed)),
+                [0.2309855, 0.5220394],
+            ),
+            (
+                "worley f1",
+                Box::new(Worley::new(seed)),
+                [-0.40751404, 0.37934816],
+            ),
+            (
+                "worley f2",
+                Box::new(Worley::new(seed).with_feature(WorleyFeature::F2)),
+                [0.2341969, 0.44062352],
+            ),
+            (
+                "worley f2 - f1",
+                Box::new(Worley::new(seed).with_feature(WorleyFeature::F2MinusF1)),
+                [-0.35828906, -0.93872464],
+            ),
+            (
+                "fbm",
+                Box::new(Fbm::new(Perlin::new(seed))),
+                [-0.04122278, 0.22409877],
+            ),
+            (
+                "billow",
+                Box::new(Billow::new(OpenSimplex::new(seed))),
+                [-0.47727025, -0.14073184],
+            ),
+            (
+                "ridged multi",
+                Box::new(RidgedMulti::new(Perlin::new(seed))),
+                [0.76175964, 0.09305143],
+            ),
+            (
+                "domain warp",
+                Box::new(DomainWarp::new(
+                    Perlin::new(seed),
+                    OpenSimplex::new(seed + 1),
+                    0.5,
+                )),
+                [-0.39273024, 0.19942486],
+            ),
+        ]
+    }
+
+    /// Grid of points spanning several lattice cells, not aligned with the lattice
+    fn get_samples() -> impl Iterator<Item = Vec3> {
+        (0..20 * 20 * 20).map(|index| {
+            let (x, y, z) = (index % 20, index / 20 % 20, index / 400);
+            Vec3::new(x as f32 * 0.53 - 5., y as f32 * 0.41 - 3., z as f32 * 0.67)
+        })
+    }
+
+    #[test]
+    fn values_are_fixed() {
+        for (name, noise, expected) in get_noises(1) {
+            for (p, expected) in POINTS.iter().zip(expected) {
+                let value = noise.get(*p);
+                assert!(
+                    (value - expected).abs() < 1e-6,
+                    "{} at {:?} is {} instead of {}",
+                    name,
+                    p,
+                    value,
+                    expected
+                );
+                assert_eq!(value.to_bits(), noise.get(*p).to_bits());
+            }
+        }
+    }
+
+    #[test]
+    fn seeds_give_different_fields() {
+        for ((name, a, _), (_, b, _)) in get_noises(1).into_iter().zip(get_noises(2)) {
+            let differ = get_samples()
+                .filter(|&p| (a.get(p) - b.get(p)).abs() > 1e-3)
+                .count();
+            assert!(differ > 4000, "{} differs at {} samples", name, differ);
+        }
+    }
+
+    #[test]
+    fn values_are_in_range() {
+        for seed in [1, 7, 12345] {
+            for (name, noise, _) in get_noises(seed) {
+                let (mut min, mut max) = (f32::MAX, f32::MIN);
+                for p in get_samples() {
+                    let value = noise.get(p);
+                    min = min.min(value);
+                    max = max.max(value);
+                }
+
+                assert!(min >= -1. && max <= 1., "{} is in [{}, {}]", name, min, max);
+                // the range is used, not squashed around zero
+                assert!(max - min > 1., "{} is in [{}, {}]", name, min, max);
+            }
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use super::{gradient, hash, NoiseFn};
+use bevy::math::Vec3;
+
+/// Squared radius of each lattice point contribution
+const KERNEL_RADIUS_SQ: f32 = 0.6;
+
+/// Scales the sum of contributions to roughly [-1, 1]
+const NORMALIZATION: f32 = 32.;
+
+/// OpenSimplex-style noise
+///
+/// Sums radial gradient kernels placed on a body-centred cubic lattice (two cubic grids offset
+/// by half a cell), which avoids the axis-aligned artifacts of Perlin noise.
+pub struct OpenSimplex {
+    seed: u64,
+}
+
+impl OpenSimplex {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    /// Sum contributions of the 8 corners of the grid cell containing "p"
+    fn sum_grid(&self, p: Vec3, seed: u64) -> f32 {
+        let floor = p.floor();
+        let mut result = 0.;
+
+        for corner in 0..8 {
+            let offset = Vec3::new(
+                (corner & 1) as f32,
+                ((corner >> 1) & 1) as f32,
+                ((corner >> 2) & 1) as f32,
+            );
+            let delta = p - floor - offset;
+            let falloff = KERNEL_RADIUS_SQ - delta.length_squared();
+            if falloff <= 0. {
+                continue;
+            }
+
+            let lattice = floor + offset;
+            let grad = gradient(hash(
+                seed,
+                lattice.x as i32,
+                lattice.y as i32,
+                lattice.z as i32,
+            ));
+            let falloff = falloff * falloff;
+            result += falloff * falloff * grad.dot(delta);
+        }
+
+        result
+    }
+}
+
+impl NoiseFn for OpenSimplex {
+    fn get(&self, p: Vec3) -> f32 {
+        let primary = self.sum_grid(p, self.seed);
+        let secondary = self.sum_grid(p + Vec3::splat(0.5), self.seed ^ 0xA5A5_A5A5_A5A5_A5A5);
+
+        (primary + secondary) * NORMALIZATION
+    }
+}
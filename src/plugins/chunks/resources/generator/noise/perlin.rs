@@ -0,0 +1,66 @@
+use super::{gradient, hash, NoiseFn};
+use bevy::math::Vec3;
+
+/// Improved Perlin gradient noise
+pub struct Perlin {
+    seed: u64,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    fn corner(&self, cell: [i32; 3], offset: [i32; 3], p: Vec3) -> f32 {
+        let grad = gradient(hash(
+            self.seed,
+            cell[0] + offset[0],
+            cell[1] + offset[1],
+            cell[2] + offset[2],
+        ));
+        grad.dot(p - Vec3::new(offset[0] as f32, offset[1] as f32, offset[2] as f32))
+    }
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6. - 15.) + 10.)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+impl NoiseFn for Perlin {
+    fn get(&self, p: Vec3) -> f32 {
+        let floor = p.floor();
+        let cell = [floor.x as i32, floor.y as i32, floor.z as i32];
+        let local = p - floor;
+
+        let u = fade(local.x);
+        let v = fade(local.y);
+        let w = fade(local.z);
+
+        let x00 = lerp(
+            self.corner(cell, [0, 0, 0], local),
+            self.corner(cell, [1, 0, 0], local),
+            u,
+        );
+        let x10 = lerp(
+            self.corner(cell, [0, 1, 0], local),
+            self.corner(cell, [1, 1, 0], local),
+            u,
+        );
+        let x01 = lerp(
+            self.corner(cell, [0, 0, 1], local),
+            self.corner(cell, [1, 0, 1], local),
+            u,
+        );
+        let x11 = lerp(
+            self.corner(cell, [0, 1, 1], local),
+            self.corner(cell, [1, 1, 1], local),
+            u,
+        );
+
+        lerp(lerp(x00, x10, v), lerp(x01, x11, v), w)
+    }
+}
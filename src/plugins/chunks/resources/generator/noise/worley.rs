@@ -0,0 +1,74 @@
+use super::{hash, hash_to_unit, NoiseFn};
+use bevy::math::Vec3;
+
+/// Value returned by [`Worley`] noise
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorleyFeature {
+    /// distance to the closest feature point
+    F1,
+    /// distance to the second closest feature point
+    F2,
+    /// difference between F2 and F1, gives cell borders
+    F2MinusF1,
+}
+
+/// Worley (cellular) noise with one feature point per unit cell
+pub struct Worley {
+    seed: u64,
+    pub feature: WorleyFeature,
+}
+
+impl Worley {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            feature: WorleyFeature::F1,
+        }
+    }
+
+    pub fn with_feature(mut self, feature: WorleyFeature) -> Self {
+        self.feature = feature;
+        self
+    }
+
+    fn feature_point(&self, x: i32, y: i32, z: i32) -> Vec3 {
+        let h = hash(self.seed, x, y, z);
+        Vec3::new(
+            x as f32 + hash_to_unit(h),
+            y as f32 + hash_to_unit(h.rotate_left(21)),
+            z as f32 + hash_to_unit(h.rotate_left(42)),
+        )
+    }
+}
+
+impl NoiseFn for Worley {
+    fn get(&self, p: Vec3) -> f32 {
+        let floor = p.floor();
+        let (cx, cy, cz) = (floor.x as i32, floor.y as i32, floor.z as i32);
+
+        let mut f1 = f32::MAX;
+        let mut f2 = f32::MAX;
+        for x in cx - 1..=cx + 1 {
+            for y in cy - 1..=cy + 1 {
+                for z in cz - 1..=cz + 1 {
+                    let distance = self.feature_point(x, y, z).distance_squared(p);
+                    if distance < f1 {
+                        f2 = f1;
+                        f1 = distance;
+                    } else if distance < f2 {
+                        f2 = distance;
+                    }
+                }
+            }
+        }
+
+        let value = match self.feature {
+            WorleyFeature::F1 => f1.sqrt(),
+            WorleyFeature::F2 => f2.sqrt(),
+            WorleyFeature::F2MinusF1 => f2.sqrt() - f1.sqrt(),
+        };
+
+        // distances are mostly within [0, 1], remap them to the common noise range
+        (value * 2. - 1.).clamp(-1., 1.)
+    }
+}
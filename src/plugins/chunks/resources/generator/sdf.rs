@@ -0,0 +1,384 @@
+use super::DensityGenerator;
+use crate::plugins::chunks::resources::{
+    pos::Position,
+    voxel::{materials, Voxel},
+};
+use bevy::math::{Quat, Vec2, Vec3, Vec3Swizzles};
+use serde::{Deserialize, Serialize};
+
+/// Signed distance field described as a tree of shapes, operations and transforms
+///
+/// Distance is negative inside of a shape. When used as [`DensityGenerator`] the distance is
+/// negated, so shapes become filled voxels. The tree can be (de)serialized with any serde format
+/// to store world description as data.
+///
+/// # Example
+/// ```
+/// use bevy::math::Vec3;
+/// use marching_cubes::plugins::chunks::resources::generator::sdf::SdfNode;
+///
+/// let shape = SdfNode::cuboid(Vec3::splat(10.))
+///     .smooth_union(SdfNode::sphere(8.).translate(Vec3::new(0., 10., 0.)), 2.)
+///     .subtract(SdfNode::cylinder(3., 20.));
+///
+/// assert!(shape.distance(Vec3::new(0., 16., 0.)) > 0.);
+/// assert!(shape.distance(Vec3::new(6., 0., 6.)) < 0.);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SdfNode {
+    Sphere {
+        radius: f32,
+    },
+    /// Axis aligned box centered at origin
+    Cuboid {
+        half_extents: Vec3,
+    },
+    /// Segment from "a" to "b" inflated by "radius", sphere if "a" and "b" are the same
+    Capsule {
+        a: Vec3,
+        b: Vec3,
+        radius: f32,
+    },
+    /// Torus lying in XZ plane
+    Torus {
+        major_radius: f32,
+        minor_radius: f32,
+    },
+    /// Half-space below the plane "dot(p, normal) = offset", zero normal fills nothing
+    Plane {
+        normal: Vec3,
+        offset: f32,
+    },
+    /// Capped cylinder along Y axis
+    Cylinder {
+        radius: f32,
+        half_height: f32,
+    },
+
+    Union(Box<SdfNode>, Box<SdfNode>),
+    /// First node with second one cut out of it
+    Subtraction(Box<SdfNode>, Box<SdfNode>),
+    Intersection(Box<SdfNode>, Box<SdfNode>),
+    /// Operations with "k" sized blend between nodes, sharp if "k" isn't positive
+    SmoothUnion(Box<SdfNode>, Box<SdfNode>, f32),
+    SmoothSubtraction(Box<SdfNode>, Box<SdfNode>, f32),
+    SmoothIntersection(Box<SdfNode>, Box<SdfNode>, f32),
+
+    Translate {
+        offset: Vec3,
+        node: Box<SdfNode>,
+    },
+    Rotate {
+        rotation: Quat,
+        node: Box<SdfNode>,
+    },
+    /// Uniform scale, zero factor collapses the node to a point at the origin
+    Scale {
+        factor: f32,
+        node: Box<SdfNode>,
+    },
+    /// Infinite repetition with given period, zero period disables repetition along the axis
+    Repeat {
+        period: Vec3,
+        node: Box<SdfNode>,
+    },
+}
+
+impl SdfNode {
+    pub fn sphere(radius: f32) -> Self {
+        Self::Sphere { radius }
+    }
+
+    pub fn cuboid(half_extents: Vec3) -> Self {
+        Self::Cuboid { half_extents }
+    }
+
+    pub fn capsule(a: Vec3, b: Vec3, radius: f32) -> Self {
+        Self::Capsule { a, b, radius }
+    }
+
+    pub fn torus(major_radius: f32, minor_radius: f32) -> Self {
+        Self::Torus {
+            major_radius,
+            minor_radius,
+        }
+    }
+
+    pub fn plane(normal: Vec3, offset: f32) -> Self {
+        Self::Plane { normal, offset }
+    }
+
+    pub fn cylinder(radius: f32, half_height: f32) -> Self {
+        Self::Cylinder {
+            radius,
+            half_height,
+        }
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self::Union(Box::new(self), Box::new(other))
+    }
+
+    pub fn subtract(self, other: Self) -> Self {
+        Self::Subtraction(Box::new(self), Box::new(other))
+    }
+
+    pub fn intersect(self, other: Self) -> Self {
+        Self::Intersection(Box::new(self), Box::new(other))
+    }
+
+    pub fn smooth_union(self, other: Self, k: f32) -> Self {
+        Self::SmoothUnion(Box::new(self), Box::new(other), k)
+    }
+
+    pub fn smooth_subtract(self, other: Self, k: f32) -> Self {
+        Self::SmoothSubtraction(Box::new(self), Box::new(other), k)
+    }
+
+    pub fn smooth_intersect(self, other: Self, k: f32) -> Self {
+        Self::SmoothIntersection(Box::new(self), Box::new(other), k)
+    }
+
+    pub fn translate(self, offset: Vec3) -> Self {
+        Self::Translate {
+            offset,
+            node: Box::new(self),
+        }
+    }
+
+    pub fn rotate(self, rotation: Quat) -> Self {
+        Self::Rotate {
+            rotation,
+            node: Box::new(self),
+        }
+    }
+
+    pub fn scale(self, factor: f32) -> Self {
+        Self::Scale {
+            factor,
+            node: Box::new(self),
+        }
+    }
+
+    pub fn repeat(self, period: Vec3) -> Self {
+        Self::Repeat {
+            period,
+            node: Box::new(self),
+        }
+    }
+
+    /// Signed distance from point "p" to the surface
+    pub fn distance(&self, p: Vec3) -> f32 {
+        match self {
+            Self::Sphere { radius } => p.length() - radius,
+            Self::Cuboid { half_extents } => {
+                let q = p.abs() - *half_extents;
+                q.max(Vec3::ZERO).length() + q.max_element().min(0.)
+            }
+            Self::Capsule { a, b, radius } => {
+                let pa = p - *a;
+                let ba = *b - *a;
+                if ba.dot(ba) == 0. {
+                    return pa.length() - radius;
+                }
+                let h = (pa.dot(ba) / ba.dot(ba)).clamp(0., 1.);
+                (pa - ba * h).length() - radius
+            }
+            Self::Torus {
+                major_radius,
+                minor_radius,
+            } => Vec2::new(p.xz().length() - major_radius, p.y).length() - minor_radius,
+            Self::Plane { normal, offset } => match normal.try_normalize() {
+                Some(normal) => p.dot(normal) - offset,
+                None => f32::MAX,
+            },
+            Self::Cylinder {
+                radius,
+                half_height,
+            } => {
+                let d = Vec2::new(p.xz().length(), p.y.abs()) - Vec2::new(*radius, *half_height);
+                d.max_element().min(0.) + d.max(Vec2::ZERO).length()
+            }
+
+            Self::Union(a, b) => a.distance(p).min(b.distance(p)),
+            Self::Subtraction(a, b) => a.distance(p).max(-b.distance(p)),
+            Self::Intersection(a, b) => a.distance(p).max(b.distance(p)),
+            Self::SmoothUnion(a, b, k) if *k > 0. => {
+                let (a, b) = (a.distance(p), b.distance(p));
+                let h = (0.5 + 0.5 * (b - a) / k).clamp(0., 1.);
+                mix(b, a, h) - k * h * (1. - h)
+            }
+            Self::SmoothSubtraction(a, b, k) if *k > 0. => {
+                let (a, b) = (a.distance(p), b.distance(p));
+                let h = (0.5 - 0.5 * (a + b) / k).clamp(0., 1.);
+                mix(a, -b, h) + k * h * (1. - h)
+            }
+            Self::SmoothIntersection(a, b, k) if *k > 0. => {
+                let (a, b) = (a.distance(p), b.distance(p));
+                let h = (0.5 - 0.5 * (b - a) / k).clamp(0., 1.);
+                mix(b, a, h) + k * h * (1. - h)
+            }
+            Self::SmoothUnion(a, b, _) => a.distance(p).min(b.distance(p)),
+            Self::SmoothSubtraction(a, b, _) => a.distance(p).max(-b.distance(p)),
+            Self::SmoothIntersection(a, b, _) => a.distance(p).max(b.distance(p)),
+
+            Self::Translate { offset, node } => node.distance(p - *offset),
+            Self::Rotate { rotation, node } => node.distance(rotation.inverse() * p),
+            Self::Scale { factor, .. } if *factor == 0. => p.length(),
+            // negative factor mirrors the node through the origin
+            Self::Scale { factor, node } => node.distance(p / *factor) * factor.abs(),
+            Self::Repeat { period, node } => node.distance(Vec3::new(
+                repeat_axis(p.x, period.x),
+                repeat_axis(p.y, period.y),
+                repeat_axis(p.z, period.z),
+            )),
+        }
+    }
+}
+
+fn mix(a: f32, b: f32, t: f32) -> f32 {
+    a * (1. - t) + b * t
+}
+
+/// Fold coordinate into [-period / 2, period / 2]
+fn repeat_axis(value: f32, period: f32) -> f32 {
+    if period == 0. {
+        return value;
+    }
+
+    value - period * (value / period).round()
+}
+
+impl DensityGenerator for SdfNode {
+    fn get_voxel(&self, pos: Position) -> Voxel {
+        Voxel {
+            value: -self.distance(pos.to_vec()),
+            material: materials::DIRT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_distance(node: &SdfNode, p: Vec3, expected: f32) {
+        let distance = node.distance(p);
+        assert!(
+            (distance - expected).abs() < 1e-4,
+            "{:?} at {}: {} instead of {}",
+            node,
+            p,
+            distance,
+            expected
+        );
+    }
+
+    #[test]
+    fn primitive_distances() {
+        let sphere = SdfNode::sphere(2.);
+        assert_distance(&sphere, Vec3::ZERO, -2.);
+        assert_distance(&sphere, Vec3::new(0., 5., 0.), 3.);
+
+        let cuboid = SdfNode::cuboid(Vec3::new(1., 2., 3.));
+        assert_distance(&cuboid, Vec3::ZERO, -1.);
+        assert_distance(&cuboid, Vec3::new(0., 4., 0.), 2.);
+        assert_distance(&cuboid, Vec3::new(4., 6., 3.), 5.);
+
+        let capsule = SdfNode::capsule(Vec3::ZERO, Vec3::new(0., 4., 0.), 1.);
+        assert_distance(&capsule, Vec3::new(3., 2., 0.), 2.);
+        assert_distance(&capsule, Vec3::new(0., 7., 0.), 2.);
+
+        let torus = SdfNode::torus(4., 1.);
+        assert_distance(&torus, Vec3::new(4., 0., 0.), -1.);
+        assert_distance(&torus, Vec3::ZERO, 3.);
+
+        let plane = SdfNode::plane(Vec3::new(0., 2., 0.), 1.);
+        assert_distance(&plane, Vec3::new(5., 3., 5.), 2.);
+        assert_distance(&plane, Vec3::new(5., -1., 5.), -2.);
+
+        let cylinder = SdfNode::cylinder(2., 3.);
+        assert_distance(&cylinder, Vec3::new(5., 0., 0.), 3.);
+        assert_distance(&cylinder, Vec3::new(0., 5., 0.), 2.);
+        assert_distance(&cylinder, Vec3::ZERO, -2.);
+    }
+
+    #[test]
+    fn boolean_operations() {
+        let a = SdfNode::sphere(2.);
+        let b = SdfNode::sphere(2.).translate(Vec3::new(3., 0., 0.));
+        let p = Vec3::new(1.5, 0., 0.);
+
+        assert_distance(&a.clone().union(b.clone()), p, -0.5);
+        assert_distance(&a.clone().intersect(b.clone()), p, -0.5);
+        assert_distance(&a.clone().subtract(b.clone()), p, 0.5);
+        assert_distance(&a.clone().union(b.clone()), Vec3::new(-1., 0., 0.), -1.);
+        assert_distance(&a.clone().intersect(b.clone()), Vec3::new(-1., 0., 0.), 2.);
+
+        // smooth operations match sharp ones far from the blend region
+        let far = Vec3::new(-1.9, 0., 0.);
+        for k in [0.5, 1.] {
+            assert_distance(&a.clone().smooth_union(b.clone(), k), far, -0.1);
+            assert_distance(&a.clone().smooth_subtract(b.clone(), k), far, -0.1);
+        }
+        // and differ by "k / 4" where both distances are equal
+        assert_distance(&a.clone().smooth_union(b.clone(), 1.), p, -0.75);
+        assert_distance(&a.clone().smooth_intersect(b.clone(), 1.), p, -0.25);
+    }
+
+    #[test]
+    fn transforms() {
+        let cuboid = SdfNode::cuboid(Vec3::new(1., 2., 1.));
+        let p = Vec3::new(0., 0., 3.);
+
+        assert_distance(&cuboid.clone().translate(Vec3::new(0., 0., 3.5)), p, -0.5);
+        let rotation = Quat::from_rotation_x(std::f32::consts::FRAC_PI_2);
+        assert_distance(&cuboid.clone().rotate(rotation), p, 1.);
+        assert_distance(&cuboid.clone().scale(2.), p, 1.);
+        assert_distance(&cuboid.clone().scale(-2.), p, 1.);
+
+        let repeated = SdfNode::sphere(1.).repeat(Vec3::new(10., 0., 0.));
+        assert_distance(&repeated, Vec3::new(30., 0., 0.), -1.);
+        assert_distance(&repeated, Vec3::new(30., 3., 0.), 2.);
+    }
+
+    #[test]
+    fn degenerate_nodes_are_finite() {
+        let nodes = [
+            SdfNode::capsule(Vec3::ONE, Vec3::ONE, 2.),
+            SdfNode::sphere(1.).scale(0.),
+            SdfNode::plane(Vec3::ZERO, 1.),
+            SdfNode::sphere(1.).smooth_union(SdfNode::sphere(1.), 0.),
+            SdfNode::sphere(1.).smooth_subtract(SdfNode::sphere(1.), 0.),
+            SdfNode::sphere(1.).smooth_intersect(SdfNode::sphere(1.), 0.),
+        ];
+        for node in nodes.iter() {
+            for p in [Vec3::ZERO, Vec3::ONE, Vec3::new(3., -2., 1.)] {
+                assert!(node.distance(p).is_finite(), "{:?} at {}", node, p);
+                assert!(node.get_voxel(Position::from_vec(p)).value.is_finite());
+            }
+        }
+
+        // capsule with a single point is a sphere
+        assert_distance(&nodes[0], Vec3::new(1., 4., 1.), 1.);
+        assert_distance(&nodes[1], Vec3::new(0., 3., 4.), 5.);
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let node = SdfNode::cuboid(Vec3::splat(10.))
+            .smooth_union(SdfNode::sphere(8.).translate(Vec3::new(0., 10., 0.)), 2.)
+            .subtract(SdfNode::cylinder(3., 20.).rotate(Quat::from_rotation_z(0.5)))
+            .intersect(SdfNode::capsule(Vec3::ZERO, Vec3::Y, 30.).scale(2.))
+            .union(SdfNode::torus(4., 1.).repeat(Vec3::new(40., 0., 40.)))
+            .smooth_intersect(SdfNode::plane(Vec3::Y, 5.), 1.)
+            .smooth_subtract(SdfNode::sphere(2.), 0.5);
+
+        let text = ron::to_string(&node).unwrap();
+        let parsed: SdfNode = ron::from_str(&text).unwrap();
+        assert_eq!(parsed, node);
+        for p in [Vec3::ZERO, Vec3::new(3., 12., -7.)] {
+            assert_eq!(parsed.distance(p).to_bits(), node.distance(p).to_bits());
+        }
+    }
+}
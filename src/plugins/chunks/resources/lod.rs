@@ -0,0 +1,28 @@
+/// Highest level of detail, chunks at this level are meshed with stride of 2^MAX_LOD voxels
+pub const MAX_LOD: u8 = 3;
+
+/// Level of detail of chunks by distance to the nearest [`ChunkLoader`]
+///
+/// [`ChunkLoader`]: crate::plugins::chunks::components::ChunkLoader
+pub struct LodSettings {
+    /// distance (in chunks) from which each next level of detail is used
+    pub distances: [i64; MAX_LOD as usize],
+}
+
+impl Default for LodSettings {
+    fn default() -> Self {
+        Self {
+            distances: [3, 5, 8],
+        }
+    }
+}
+
+impl LodSettings {
+    /// Level of detail for chunk "distance" chunks away from the loader
+    pub fn get_lod(&self, distance: i64) -> u8 {
+        self.distances
+            .iter()
+            .take_while(|&&lod_distance| distance >= lod_distance)
+            .count() as u8
+    }
+}
@@ -0,0 +1,20 @@
+use self::terrain::TerrainMaterial;
+use super::mesh::palette::MaterialPalette;
+use bevy::prelude::*;
+
+pub mod terrain;
+
+/// Material shared by meshes of all chunks
+pub struct ChunkMaterial(pub Handle<TerrainMaterial>);
+
+impl FromWorld for ChunkMaterial {
+    fn from_world(world: &mut World) -> Self {
+        let material = {
+            let mut images = world.resource_mut::<Assets<Image>>();
+            TerrainMaterial::from_palette(&MaterialPalette::default(), &mut images)
+        };
+
+        let mut materials = world.resource_mut::<Assets<TerrainMaterial>>();
+        Self(materials.add(material))
+    }
+}
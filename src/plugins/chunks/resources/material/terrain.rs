@@ -0,0 +1,290 @@
+use crate::plugins::chunks::resources::{
+    mesh::{palette::MaterialPalette, ATTRIBUTE_MATERIAL_WEIGHTS_0, ATTRIBUTE_MATERIAL_WEIGHTS_1},
+    voxel::MAX_MATERIALS,
+};
+use bevy::{
+    ecs::system::{lifetimeless::SRes, SystemParamItem},
+    pbr::{MaterialPipeline, MaterialPlugin},
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        mesh::MeshVertexBufferLayout,
+        render_asset::{PrepareAssetError, RenderAsset, RenderAssets},
+        render_resource::{
+            std140::{AsStd140, Std140},
+            AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, Buffer,
+            BufferBindingType, BufferInitDescriptor, BufferSize, BufferUsages, Extent3d,
+            FilterMode, RenderPipelineDescriptor, SamplerBindingType, SamplerDescriptor,
+            ShaderStages, SpecializedMeshPipelineError, TextureDimension, TextureFormat,
+            TextureSampleType, TextureViewDescriptor, TextureViewDimension,
+        },
+        renderer::RenderDevice,
+    },
+};
+
+pub const TERRAIN_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x7465_7272_6169_6e00);
+
+/// Registers [`TerrainMaterial`] and its shader
+///
+/// Added by every chunks plugin, does nothing if the material is already registered.
+pub struct TerrainMaterialPlugin;
+
+impl Plugin for TerrainMaterialPlugin {
+    fn build(&self, app: &mut App) {
+        if app.world.contains_resource::<Assets<TerrainMaterial>>() {
+            return;
+        }
+
+        app.world.resource_mut::<Assets<Shader>>().set_untracked(
+            TERRAIN_SHADER_HANDLE,
+            Shader::from_wgsl(include_str!("terrain.wgsl")),
+        );
+
+        app.add_plugin(MaterialPlugin::<TerrainMaterial>::default());
+    }
+}
+
+/// Material of chunk meshes with a texture layer per voxel material
+///
+/// Textures are projected along world axes (triplanar mapping), so meshes don't need uvs,
+/// layers are blended by material weights of vertices.
+///
+/// Lighting is simplified: lambertian diffuse from directional lights and ambient light only.
+/// Point and spot lights, shadows and specular highlights of [`StandardMaterial`] are not
+/// supported.
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "0b6f6b52-3d0e-4a56-9d3c-52e8b0f2a7d1"]
+pub struct TerrainMaterial {
+    /// Array texture with a color layer for each voxel material
+    pub albedo: Handle<Image>,
+    /// Array texture with a tangent space normal map layer for each voxel material
+    pub normal_map: Handle<Image>,
+    /// Size of a single texture repeat in world units
+    pub texture_size: f32,
+    /// Higher values give sharper transitions between projections
+    pub blend_sharpness: f32,
+    pub normal_strength: f32,
+}
+
+impl TerrainMaterial {
+    /// "albedo" and "normal_map" must be array textures with [`MAX_MATERIALS`] layers,
+    /// see [`Image::reinterpret_stacked_2d_as_array`]
+    pub fn new(albedo: Handle<Image>, normal_map: Handle<Image>) -> Self {
+        Self {
+            albedo,
+            normal_map,
+            texture_size: 8.,
+            blend_sharpness: 4.,
+            normal_strength: 1.,
+        }
+    }
+
+    /// Untextured material with solid colors of "palette" and flat normal maps
+    pub fn from_palette(palette: &MaterialPalette, images: &mut Assets<Image>) -> Self {
+        let albedo = palette
+            .colors
+            .iter()
+            .flat_map(|color| color.as_rgba_f32().map(|channel| (channel * 255.) as u8))
+            .collect();
+        let normal_map = [128, 128, 255, 255].repeat(MAX_MATERIALS);
+
+        Self::new(
+            images.add(get_layers_image(albedo, TextureFormat::Rgba8UnormSrgb)),
+            images.add(get_layers_image(normal_map, TextureFormat::Rgba8Unorm)),
+        )
+    }
+
+    pub fn with_texture_size(mut self, texture_size: f32) -> Self {
+        self.texture_size = texture_size;
+        self
+    }
+
+    pub fn with_blend_sharpness(mut self, blend_sharpness: f32) -> Self {
+        self.blend_sharpness = blend_sharpness;
+        self
+    }
+
+    pub fn with_normal_strength(mut self, normal_strength: f32) -> Self {
+        self.normal_strength = normal_strength;
+        self
+    }
+}
+
+/// 1x1 array texture with a layer per voxel material
+fn get_layers_image(data: Vec<u8>, format: TextureFormat) -> Image {
+    let size = Extent3d {
+        width: 1,
+        height: 1,
+        depth_or_array_layers: MAX_MATERIALS as u32,
+    };
+
+    Image::new(size, TextureDimension::D2, data, format)
+}
+
+#[derive(Clone, AsStd140)]
+struct TerrainMaterialUniformData {
+    texture_scale: f32,
+    blend_sharpness: f32,
+    normal_strength: f32,
+}
+
+pub struct GpuTerrainMaterial {
+    _buffer: Buffer,
+    bind_group: BindGroup,
+}
+
+impl RenderAsset for TerrainMaterial {
+    type ExtractedAsset = TerrainMaterial;
+    type PreparedAsset = GpuTerrainMaterial;
+    type Param = (
+        SRes<RenderDevice>,
+        SRes<MaterialPipeline<TerrainMaterial>>,
+        SRes<RenderAssets<Image>>,
+    );
+
+    fn extract_asset(&self) -> Self::ExtractedAsset {
+        self.clone()
+    }
+
+    fn prepare_asset(
+        material: Self::ExtractedAsset,
+        (render_device, pipeline, gpu_images): &mut SystemParamItem<Self::Param>,
+    ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+        let (albedo, normal_map) = match (
+            gpu_images.get(&material.albedo),
+            gpu_images.get(&material.normal_map),
+        ) {
+            (Some(albedo), Some(normal_map)) => (albedo, normal_map),
+            _ => return Err(PrepareAssetError::RetryNextUpdate(material)),
+        };
+
+        // default views of single layer images are not arrays
+        let array_view = TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::D2Array),
+            ..default()
+        };
+        let albedo_view = albedo.texture.create_view(&array_view);
+        let normal_map_view = normal_map.texture.create_view(&array_view);
+
+        // textures are tiled over the whole world
+        let sampler = render_device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::Repeat,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            ..default()
+        });
+
+        let value = TerrainMaterialUniformData {
+            texture_scale: 1. / material.texture_size,
+            blend_sharpness: material.blend_sharpness,
+            normal_strength: material.normal_strength,
+        };
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("terrain_material_uniform_buffer"),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            contents: value.as_std140().as_bytes(),
+        });
+
+        let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&albedo_view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::TextureView(&normal_map_view),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::Sampler(&sampler),
+                },
+            ],
+            label: Some("terrain_material_bind_group"),
+            layout: &pipeline.material_layout,
+        });
+
+        Ok(GpuTerrainMaterial {
+            _buffer: buffer,
+            bind_group,
+        })
+    }
+}
+
+impl Material for TerrainMaterial {
+    fn vertex_shader(_asset_server: &AssetServer) -> Option<Handle<Shader>> {
+        Some(TERRAIN_SHADER_HANDLE.typed())
+    }
+
+    fn fragment_shader(_asset_server: &AssetServer) -> Option<Handle<Shader>> {
+        Some(TERRAIN_SHADER_HANDLE.typed())
+    }
+
+    fn bind_group(material: &GpuTerrainMaterial) -> &BindGroup {
+        &material.bind_group
+    }
+
+    fn bind_group_layout(render_device: &RenderDevice) -> BindGroupLayout {
+        let texture_entry = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Texture {
+                multisampled: false,
+                sample_type: TextureSampleType::Float { filterable: true },
+                view_dimension: TextureViewDimension::D2Array,
+            },
+            count: None,
+        };
+
+        render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(
+                            TerrainMaterialUniformData::std140_size_static() as u64,
+                        ),
+                    },
+                    count: None,
+                },
+                texture_entry(1),
+                texture_entry(2),
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("terrain_material_layout"),
+        })
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayout,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        // uvs are not used, material weights take their place
+        let vertex_layout = layout.get_layout(&[
+            Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+            Mesh::ATTRIBUTE_NORMAL.at_shader_location(1),
+            ATTRIBUTE_MATERIAL_WEIGHTS_0.at_shader_location(2),
+            ATTRIBUTE_MATERIAL_WEIGHTS_1.at_shader_location(3),
+        ])?;
+        descriptor.vertex.buffers = vec![vertex_layout];
+
+        Ok(())
+    }
+}
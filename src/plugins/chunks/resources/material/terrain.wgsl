@@ -0,0 +1,141 @@
+#import bevy_pbr::mesh_view_bind_group
+#import bevy_pbr::mesh_struct
+
+struct TerrainMaterial {
+    texture_scale: f32;
+    blend_sharpness: f32;
+    normal_strength: f32;
+};
+
+[[group(1), binding(0)]]
+var<uniform> material: TerrainMaterial;
+[[group(1), binding(1)]]
+var albedo_texture: texture_2d_array<f32>;
+[[group(1), binding(2)]]
+var normal_map_texture: texture_2d_array<f32>;
+[[group(1), binding(3)]]
+var texture_sampler: sampler;
+
+[[group(2), binding(0)]]
+var<uniform> mesh: Mesh;
+
+let PI: f32 = 3.141592653589793;
+let MAX_MATERIALS: i32 = 8;
+
+struct Vertex {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+    [[location(2)]] weights_0: vec4<f32>;
+    [[location(3)]] weights_1: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] world_position: vec4<f32>;
+    [[location(1)]] world_normal: vec3<f32>;
+    [[location(2)]] weights_0: vec4<f32>;
+    [[location(3)]] weights_1: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    out.world_position = mesh.model * vec4<f32>(vertex.position, 1.0);
+    out.clip_position = view.view_proj * out.world_position;
+    out.world_normal = mat3x3<f32>(
+        mesh.inverse_transpose_model[0].xyz,
+        mesh.inverse_transpose_model[1].xyz,
+        mesh.inverse_transpose_model[2].xyz
+    ) * vertex.normal;
+    out.weights_0 = vertex.weights_0;
+    out.weights_1 = vertex.weights_1;
+    return out;
+}
+
+struct FragmentInput {
+    [[location(0)]] world_position: vec4<f32>;
+    [[location(1)]] world_normal: vec3<f32>;
+    [[location(2)]] weights_0: vec4<f32>;
+    [[location(3)]] weights_1: vec4<f32>;
+};
+
+fn get_weight(in: FragmentInput, layer: i32) -> f32 {
+    if (layer < 4) {
+        return in.weights_0[layer];
+    }
+    return in.weights_1[layer - 4];
+}
+
+fn unpack_normal(value: vec4<f32>) -> vec3<f32> {
+    let normal = value.xyz * 2.0 - 1.0;
+    return vec3<f32>(normal.xy * material.normal_strength, normal.z);
+}
+
+[[stage(fragment)]]
+fn fragment(in: FragmentInput) -> [[location(0)]] vec4<f32> {
+    let normal = normalize(in.world_normal);
+
+    // weights of projections along x, y and z axes
+    var blend = pow(abs(normal), vec3<f32>(material.blend_sharpness));
+    blend = blend / (blend.x + blend.y + blend.z);
+
+    let position = in.world_position.xyz * material.texture_scale;
+    let uv_x = position.zy;
+    let uv_y = position.xz;
+    let uv_z = position.xy;
+
+    // derivatives are taken before branching on material weights
+    let ddx_x = dpdx(uv_x);
+    let ddy_x = dpdy(uv_x);
+    let ddx_y = dpdx(uv_y);
+    let ddy_y = dpdy(uv_y);
+    let ddx_z = dpdx(uv_z);
+    let ddy_z = dpdy(uv_z);
+
+    var albedo = vec4<f32>(0.0);
+    var tangent_x = vec3<f32>(0.0);
+    var tangent_y = vec3<f32>(0.0);
+    var tangent_z = vec3<f32>(0.0);
+    for (var layer: i32 = 0; layer < MAX_MATERIALS; layer = layer + 1) {
+        let weight = get_weight(in, layer);
+        if (weight <= 0.001) {
+            continue;
+        }
+
+        albedo = albedo + weight * (
+            textureSampleGrad(albedo_texture, texture_sampler, uv_x, layer, ddx_x, ddy_x) * blend.x +
+            textureSampleGrad(albedo_texture, texture_sampler, uv_y, layer, ddx_y, ddy_y) * blend.y +
+            textureSampleGrad(albedo_texture, texture_sampler, uv_z, layer, ddx_z, ddy_z) * blend.z
+        );
+
+        tangent_x = tangent_x + weight * unpack_normal(
+            textureSampleGrad(normal_map_texture, texture_sampler, uv_x, layer, ddx_x, ddy_x)
+        );
+        tangent_y = tangent_y + weight * unpack_normal(
+            textureSampleGrad(normal_map_texture, texture_sampler, uv_y, layer, ddx_y, ddy_y)
+        );
+        tangent_z = tangent_z + weight * unpack_normal(
+            textureSampleGrad(normal_map_texture, texture_sampler, uv_z, layer, ddx_z, ddy_z)
+        );
+    }
+
+    // whiteout blend of tangent space normals with the surface normal of each projection
+    let normal_x = vec3<f32>(tangent_x.xy + normal.zy, abs(tangent_x.z) * normal.x);
+    let normal_y = vec3<f32>(tangent_y.xy + normal.xz, abs(tangent_y.z) * normal.y);
+    let normal_z = vec3<f32>(tangent_z.xy + normal.xy, abs(tangent_z.z) * normal.z);
+    let mapped_normal = normalize(
+        normal_x.zyx * blend.x +
+        normal_y.xzy * blend.y +
+        normal_z.xyz * blend.z
+    );
+
+    // lambertian diffuse with ambient light
+    var light = lights.ambient_color.rgb;
+    for (var i: u32 = 0u; i < lights.n_directional_lights; i = i + 1u) {
+        let directional = lights.directional_lights[i];
+        let n_dot_l = max(dot(mapped_normal, directional.direction_to_light), 0.0);
+        light = light + directional.color.rgb * n_dot_l / PI;
+    }
+
+    return vec4<f32>(albedo.rgb * light, 1.0);
+}
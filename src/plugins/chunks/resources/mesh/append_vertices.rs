@@ -1,17 +1,22 @@
 use super::{
-    edge_midpoints::{EdgeMidpointsIndices, CUBE_EDGES_COUNT},
-    triangulation_table::get_triangles_by_voxels,
-    BlockOfVoxels, Vertex,
+    edge_midpoints::EDGE_CORNERS, get_material_weights,
+    triangulation_table::get_triangles_by_voxels, BlockOfVoxels, MeshData, Vertex,
 };
-use crate::plugins::chunks::resources::{chunk::Chunk, pos::Position, voxel::Voxel};
-use bevy::{math::Vec3, prelude::Color};
+use crate::plugins::chunks::resources::{
+    chunk::{CHUNK_VOLUME, CHUNK_VOXELS_SIZE},
+    chunk_view::ChunkView,
+    pos::Position,
+    voxel::Voxel,
+};
+use bevy::math::Vec3;
 
-/// Append vertices for 8 voxels at position "pos" based on triangulation table
+/// Append vertices for 8 voxels at position "pos" based on triangulation table  
+/// Voxels are taken "stride" voxels apart to mesh chunk at lower level of detail
 ///
 /// # Example
 /// one of 256 possible cases:  
-/// 3 voxels at the bottom are filled(with value > 0), the rest are empty (value <= 0)  
-/// ```
+/// 3 voxels at the bottom are filled (with value >= iso level), the rest are empty  
+/// ```text
 ///        -1                             -2
 ///         +-   -   -   -   -   -   -   - +
 ///        /                              /
@@ -32,81 +37,143 @@ use bevy::{math::Vec3, prelude::Color};
 ///   3 +-       -       -       -     . -1
 /// ```
 ///
-pub fn append_vertices(pos: Position, chunk: &Chunk, vertices: &mut Vec<Vertex>) {
-    let voxels = get_voxels_for_vertex(chunk, pos);
-    let midpoints = get_vertex_midpoints(voxels);
+pub fn append_vertices(
+    pos: Position,
+    stride: i64,
+    iso_level: f32,
+    view: &ChunkView,
+    data: &mut MeshData,
+    cache: &mut EdgeVertexCache,
+) {
+    let voxels = get_voxels_for_vertex(view, pos, stride);
 
-    let triangles = get_triangles_by_voxels(voxels);
+    let triangles = get_triangles_by_voxels(voxels, iso_level);
 
     let mut triangle_offset = 0;
 
     // iterate through triangles
     while triangles[triangle_offset] != -1 {
         // get 3 triangle points and append them to the mesh
-        let a = midpoints[triangles[triangle_offset] as usize];
-        let b = midpoints[triangles[triangle_offset + 1] as usize];
-        let c = midpoints[triangles[triangle_offset + 2] as usize];
+        let corners = [
+            triangles[triangle_offset] as usize,
+            triangles[triangle_offset + 1] as usize,
+            triangles[triangle_offset + 2] as usize,
+        ]
+        .map(|edge| get_edge_vertex(pos, stride, iso_level, &voxels, edge, data, cache));
 
-        append_triangle(pos, vertices, a, b, c, Color::rgb(0.5, 0.45, 0.4));
+        append_triangle(data, corners);
 
         triangle_offset += 3;
     }
 }
 
-fn append_triangle(
-    pos: Position,
-    vertices: &mut Vec<Vertex>,
-    a: Vec3,
-    b: Vec3,
-    c: Vec3,
-    color: Color,
-) {
-    let pos_vec = Vec3::new(pos.x as f32, pos.y as f32, pos.z as f32);
+/// Index of vertex at each cube edge, shared by all cubes around the edge
+pub struct EdgeVertexCache {
+    indices: Vec<u32>,
+}
+
+impl EdgeVertexCache {
+    const EMPTY: u32 = u32::MAX;
 
-    let a = a + pos_vec;
-    let b = b + pos_vec;
-    let c = c + pos_vec;
+    pub fn new() -> Self {
+        Self {
+            indices: vec![Self::EMPTY; CHUNK_VOLUME * 3],
+        }
+    }
 
-    let normal = (c - a).cross(b - a).normalize();
+    /// Edge is identified by its lower corner and axis it goes along
+    fn get_index(corner: Position, axis: usize) -> usize {
+        (corner.x as usize
+            + (corner.y as usize) * CHUNK_VOXELS_SIZE
+            + (corner.z as usize) * CHUNK_VOXELS_SIZE * CHUNK_VOXELS_SIZE)
+            * 3
+            + axis
+    }
+}
 
-    vertices.push(Vertex {
-        color,
-        normal,
-        pos: a,
-    });
-    vertices.push(Vertex {
-        color,
-        normal,
-        pos: b,
-    });
-    vertices.push(Vertex {
-        color,
-        normal,
-        pos: c,
+impl Default for EdgeVertexCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Get index of vertex at cube edge, the vertex is created if no other cube added it before
+pub(super) fn get_edge_vertex(
+    pos: Position,
+    stride: i64,
+    iso_level: f32,
+    voxels: &BlockOfVoxels,
+    edge: usize,
+    data: &mut MeshData,
+    cache: &mut EdgeVertexCache,
+) -> u32 {
+    let [a, b] = EDGE_CORNERS[edge];
+    let axis = (0..3).find(|&axis| a[axis] != b[axis]).unwrap_or(0);
+    let corner = pos + Position::new(a[0] as i64, a[1] as i64, a[2] as i64) * stride;
+
+    let cache_index = EdgeVertexCache::get_index(corner, axis);
+    if cache.indices[cache_index] != EdgeVertexCache::EMPTY {
+        return cache.indices[cache_index];
+    }
+
+    let index = data.vertices.len() as u32;
+    // empty voxels have no material, so vertex gets material of the filled one
+    let filled = if voxels[a[0]][a[1]][a[2]].is_filled(iso_level) {
+        a
+    } else {
+        b
+    };
+    data.vertices.push(Vertex {
+        pos: get_midpoint_data(voxels, a, b, iso_level) * stride as f32 + pos.to_vec(),
+        normal: Vec3::ZERO,
+        materials: get_material_weights(voxels[filled[0]][filled[1]][filled[2]].material),
     });
+    cache.indices[cache_index] = index;
+
+    index
+}
+
+/// Append triangle and add its face normal to normals of its vertices
+///
+/// Face normal of "corners" points towards lower density, the triangle is wound counter-clockwise
+/// around it, so its front face looks at the empty side.
+pub(super) fn append_triangle(data: &mut MeshData, corners: [u32; 3]) {
+    let [a, b, c] = corners.map(|index| data.vertices[index as usize].pos);
+
+    // not normalized, so bigger triangles have more influence on shared vertex normal
+    let normal = (c - a).cross(b - a);
+
+    for index in [corners[0], corners[2], corners[1]] {
+        data.vertices[index as usize].normal += normal;
+        data.indices.push(index);
+    }
 }
 
 /// Returns a 3D array with 8 voxels as cube vertices.
-fn get_voxels_for_vertex(chunk: &Chunk, base_pos: Position) -> BlockOfVoxels {
+pub(super) fn get_voxels_for_vertex(
+    view: &ChunkView,
+    base_pos: Position,
+    stride: i64,
+) -> BlockOfVoxels {
     let voxels: [[[Voxel; 2]; 2]; 2] = [
         [
             [
-                chunk.get_voxel(base_pos + Position::new(0, 0, 0)),
-                chunk.get_voxel(base_pos + Position::new(0, 0, 1)),
+                view.get_voxel(base_pos + Position::new(0, 0, 0) * stride),
+                view.get_voxel(base_pos + Position::new(0, 0, 1) * stride),
             ],
             [
-                chunk.get_voxel(base_pos + Position::new(0, 1, 0)),
-                chunk.get_voxel(base_pos + Position::new(0, 1, 1)),
+                view.get_voxel(base_pos + Position::new(0, 1, 0) * stride),
+                view.get_voxel(base_pos + Position::new(0, 1, 1) * stride),
             ],
         ],
         [
             [
-                chunk.get_voxel(base_pos + Position::new(1, 0, 0)),
-                chunk.get_voxel(base_pos + Position::new(1, 0, 1)),
+                view.get_voxel(base_pos + Position::new(1, 0, 0) * stride),
+                view.get_voxel(base_pos + Position::new(1, 0, 1) * stride),
             ],
             [
-                chunk.get_voxel(base_pos + Position::new(1, 1, 0)),
-                chunk.get_voxel(base_pos + Position::new(1, 1, 1)),
+                view.get_voxel(base_pos + Position::new(1, 1, 0) * stride),
+                view.get_voxel(base_pos + Position::new(1, 1, 1) * stride),
             ],
         ],
     ];
@@ -114,58 +181,73 @@ fn get_voxels_for_vertex(chunk: &Chunk, base_pos: Position) -> BlockOfVoxels {
     voxels
 }
 
-fn mix_positions(a: Vec3, b: Vec3, a_val: f32, b_val: f32) -> Vec3 {
-    let transition = a_val / (a_val - b_val);
+/// Position of "iso_level" between values "a" and "b" in range from 0 to 1
+///
+/// Values at both ends of crossed edge can't be equal, but the result is clamped anyway,
+/// so vertices never leave their edge.
+pub(super) fn get_transition(a: f32, b: f32, iso_level: f32) -> f32 {
+    if a == b {
+        return 0.5;
+    }
 
-    a * (1.0 - transition) + b * transition
+    ((iso_level - a) / (b - a)).clamp(0., 1.)
 }
 
-fn get_midpoint_data(voxels: &BlockOfVoxels, a: [usize; 3], b: [usize; 3]) -> Vec3 {
+fn get_midpoint_data(voxels: &BlockOfVoxels, a: [usize; 3], b: [usize; 3], iso_level: f32) -> Vec3 {
     let voxel_a = voxels[a[0]][a[1]][a[2]];
     let voxel_b = voxels[b[0]][b[1]][b[2]];
 
     let vec_a = Vec3::new(a[0] as f32, a[1] as f32, a[2] as f32);
     let vec_b = Vec3::new(b[0] as f32, b[1] as f32, b[2] as f32);
 
-    if voxel_a.value > 0. && voxel_b.value <= 0. {
-        mix_positions(vec_a, vec_b, voxel_a.value, voxel_b.value)
-    } else if voxel_a.value <= 0. && voxel_b.value > 0. {
-        mix_positions(vec_b, vec_a, voxel_b.value, voxel_a.value)
-    } else {
-        Vec3::default()
-    }
+    vec_a.lerp(
+        vec_b,
+        get_transition(voxel_a.value, voxel_b.value, iso_level),
+    )
 }
 
-/// get real vertex position for each midpoint
-fn get_vertex_midpoints(voxels: BlockOfVoxels) -> [Vec3; CUBE_EDGES_COUNT] {
-    let mut result = [Vec3::default(); CUBE_EDGES_COUNT];
-
-    result[EdgeMidpointsIndices::BottomSouth as usize] =
-        get_midpoint_data(&voxels, [0, 0, 0], [1, 0, 0]);
-    result[EdgeMidpointsIndices::BottomEast as usize] =
-        get_midpoint_data(&voxels, [1, 0, 0], [1, 0, 1]);
-    result[EdgeMidpointsIndices::BottomNorth as usize] =
-        get_midpoint_data(&voxels, [0, 0, 1], [1, 0, 1]);
-    result[EdgeMidpointsIndices::BottomWest as usize] =
-        get_midpoint_data(&voxels, [0, 0, 0], [0, 0, 1]);
-
-    result[EdgeMidpointsIndices::NorthEast as usize] =
-        get_midpoint_data(&voxels, [1, 0, 1], [1, 1, 1]);
-    result[EdgeMidpointsIndices::NorthWest as usize] =
-        get_midpoint_data(&voxels, [0, 0, 1], [0, 1, 1]);
-    result[EdgeMidpointsIndices::SouthEast as usize] =
-        get_midpoint_data(&voxels, [1, 0, 0], [1, 1, 0]);
-    result[EdgeMidpointsIndices::SouthWest as usize] =
-        get_midpoint_data(&voxels, [0, 0, 0], [0, 1, 0]);
-
-    result[EdgeMidpointsIndices::TopSouth as usize] =
-        get_midpoint_data(&voxels, [0, 1, 0], [1, 1, 0]);
-    result[EdgeMidpointsIndices::TopEast as usize] =
-        get_midpoint_data(&voxels, [1, 1, 0], [1, 1, 1]);
-    result[EdgeMidpointsIndices::TopNorth as usize] =
-        get_midpoint_data(&voxels, [0, 1, 1], [1, 1, 1]);
-    result[EdgeMidpointsIndices::TopWest as usize] =
-        get_midpoint_data(&voxels, [0, 1, 0], [0, 1, 1]);
-
-    result
+#[cfg(test)]
+mod tests {
+    use crate::plugins::chunks::resources::{
+        chunk::Chunk,
+        generator::sdf::SdfNode,
+        mesh::{mesher::MesherKind, MeshSettings},
+        pos::Position,
+    };
+    use bevy::{math::Vec3, utils::HashSet};
+
+    #[test]
+    fn edge_vertices_are_welded() {
+        let sphere = SdfNode::sphere(9.5).translate(Vec3::splat(16.));
+        let chunk = Chunk::new(Position::new(0, 0, 0), &sphere);
+
+        // both meshers share vertices through the edge cache
+        for mesher in [
+            MesherKind::MarchingCubes,
+            MesherKind::AsymptoticMarchingCubes,
+        ] {
+            let settings = MeshSettings {
+                mesher,
+                ..Default::default()
+            };
+            let data = chunk.generate_vertices(&settings, &sphere);
+            assert!(!data.indices.is_empty());
+
+            // closed surface has about twice as many triangles as vertices
+            assert!(
+                data.vertices.len() * 4 < data.indices.len(),
+                "{:?} has {} vertices for {} indices",
+                mesher,
+                data.vertices.len(),
+                data.indices.len()
+            );
+
+            let positions: HashSet<[u32; 3]> = data
+                .vertices
+                .iter()
+                .map(|vertex| vertex.pos.to_array().map(f32::to_bits))
+                .collect();
+            assert_eq!(positions.len(), data.vertices.len(), "{:?}", mesher);
+        }
+    }
 }
@@ -0,0 +1,293 @@
+use super::{
+    append_vertices::{append_triangle, get_edge_vertex, get_voxels_for_vertex, EdgeVertexCache},
+    blend_material_weights,
+    edge_midpoints::{CUBE_EDGES_COUNT, EDGE_CORNERS},
+    mesher::Mesher,
+    BlockOfVoxels, MeshData, Vertex,
+};
+use crate::plugins::chunks::resources::{
+    chunk::CHUNK_REAL_SIZE, chunk_view::ChunkView, pos::Position,
+};
+use bevy::math::Vec3;
+
+/// Marching cubes without triangulation table, ambiguous faces are resolved with the
+/// asymptotic decider
+///
+/// Surface of each cube face is decided from the face's own 4 voxels, so both cubes sharing
+/// the face produce the same segments. Segments are chained into closed loops and
+/// triangulated, which gives watertight and manifold output: inside of the chunk each edge is
+/// shared by exactly 2 triangles. Ambiguities inside of a cube (tunnels of Marching Cubes 33)
+/// are not resolved, they don't affect topology of the faces.
+pub struct AsymptoticMarchingCubes;
+
+/// Cube face as edges in counter-clockwise order viewed from outside of the cube,
+/// edge "i" goes from corner "i" to corner "i + 1"
+struct CubeFace {
+    corners: [[usize; 3]; 4],
+    edges: [usize; 4],
+}
+
+impl AsymptoticMarchingCubes {
+    fn get_faces() -> [CubeFace; 6] {
+        let find_edge = |a: [usize; 3], b: [usize; 3]| {
+            (0..CUBE_EDGES_COUNT)
+                .find(|&edge| EDGE_CORNERS[edge] == [a, b] || EDGE_CORNERS[edge] == [b, a])
+                .expect("corners are connected by an edge")
+        };
+
+        [0, 1, 2, 3, 4, 5].map(|face| {
+            let (axis, side) = (face / 2, face % 2);
+            let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+
+            // u x v points along the axis, so the order is reversed for the lower face
+            let mut square = [[0, 0], [1, 0], [1, 1], [0, 1]];
+            if side == 0 {
+                square.reverse();
+            }
+
+            let corners = square.map(|[corner_u, corner_v]| {
+                let mut corner = [0; 3];
+                corner[axis] = side;
+                corner[u] = corner_u;
+                corner[v] = corner_v;
+                corner
+            });
+            let edges = [0, 1, 2, 3].map(|i| find_edge(corners[i], corners[(i + 1) % 4]));
+
+            CubeFace { corners, edges }
+        })
+    }
+
+    /// Directed segments of the surface on the face, from the edge where walk around the face
+    /// leaves filled voxels to the edge where it enters them
+    fn append_face_segments(
+        face: &CubeFace,
+        voxels: &BlockOfVoxels,
+        iso_level: f32,
+        segments: &mut [usize],
+    ) {
+        let is_filled = face
+            .corners
+            .map(|[x, y, z]| voxels[x][y][z].is_filled(iso_level));
+        let values = face
+            .corners
+            .map(|[x, y, z]| voxels[x][y][z].value - iso_level);
+
+        let exits: Vec<usize> = (0..4)
+            .filter(|&i| is_filled[i] && !is_filled[(i + 1) % 4])
+            .collect();
+        let entries: Vec<usize> = (0..4)
+            .filter(|&i| !is_filled[i] && is_filled[(i + 1) % 4])
+            .collect();
+
+        if exits.len() == 1 {
+            segments[face.edges[exits[0]]] = face.edges[entries[0]];
+            return;
+        }
+
+        // ambiguous face, filled voxels are connected if bilinear saddle point is filled
+        let [a, b, c, d] = values;
+        let denominator = a + c - b - d;
+        let saddle = if denominator != 0. {
+            (a * c - b * d) / denominator
+        } else {
+            0.
+        };
+
+        for exit in exits {
+            let entry = (if saddle >= 0. { exit + 1 } else { exit + 3 }) % 4;
+            segments[face.edges[exit]] = face.edges[entry];
+        }
+    }
+}
+
+impl Mesher for AsymptoticMarchingCubes {
+    fn generate(&self, view: &ChunkView, stride: i64, iso_level: f32, data: &mut MeshData) {
+        let faces = Self::get_faces();
+        let mut cache = EdgeVertexCache::new();
+
+        for x in (0..CHUNK_REAL_SIZE).step_by(stride as usize) {
+            for y in (0..CHUNK_REAL_SIZE).step_by(stride as usize) {
+                for z in (0..CHUNK_REAL_SIZE).step_by(stride as usize) {
+                    let pos = Position::new(x as i64, y as i64, z as i64);
+                    let voxels = get_voxels_for_vertex(view, pos, stride);
+
+                    // next edge of the loop after each crossed edge
+                    let mut segments = [usize::MAX; CUBE_EDGES_COUNT];
+                    for face in faces.iter() {
+                        Self::append_face_segments(face, &voxels, iso_level, &mut segments);
+                    }
+
+                    let mut visited = [false; CUBE_EDGES_COUNT];
+                    for start in 0..CUBE_EDGES_COUNT {
+                        if segments[start] == usize::MAX || visited[start] {
+                            continue;
+                        }
+
+                        let mut edges = Vec::new();
+                        let mut edge = start;
+                        while !visited[edge] {
+                            visited[edge] = true;
+                            edges.push(edge);
+                            edge = segments[edge];
+                        }
+
+                        let corners: Vec<u32> = edges
+                            .iter()
+                            .map(|&edge| {
+                                get_edge_vertex(
+                                    pos, stride, iso_level, &voxels, edge, data, &mut cache,
+                                )
+                            })
+                            .collect();
+                        append_loop(data, &edges, &corners);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Check if cube edges lie on the same cube face
+fn is_on_same_face(a: usize, b: usize) -> bool {
+    let corners = [EDGE_CORNERS[a], EDGE_CORNERS[b]].concat();
+    (0..3).any(|axis| {
+        corners
+            .iter()
+            .all(|corner| corner[axis] == corners[0][axis])
+    })
+}
+
+/// Triangulate loop of vertices at cube "edges"
+///
+/// Loop is fanned from a vertex whose diagonals don't lie on cube faces, otherwise they could
+/// overlap with triangles of the neighbour cube. If there is no such vertex the loop is split
+/// around its center.
+fn append_loop(data: &mut MeshData, edges: &[usize], corners: &[u32]) {
+    let len = corners.len();
+    let apex = (0..len).find(|&apex| {
+        (2..len - 1).all(|offset| !is_on_same_face(edges[apex], edges[(apex + offset) % len]))
+    });
+
+    if let Some(apex) = apex {
+        for offset in 1..len - 1 {
+            append_triangle(
+                data,
+                [
+                    corners[apex],
+                    corners[(apex + offset) % len],
+                    corners[(apex + offset + 1) % len],
+                ],
+            );
+        }
+        return;
+    }
+
+    let center = corners.iter().fold(Vec3::ZERO, |sum, &index| {
+        sum + data.vertices[index as usize].pos
+    }) / len as f32;
+    let center_index = data.vertices.len() as u32;
+    let materials = blend_material_weights(
+        corners
+            .iter()
+            .map(|&index| data.vertices[index as usize].materials),
+    );
+    data.vertices.push(Vertex {
+        pos: center,
+        normal: Vec3::ZERO,
+        materials,
+    });
+
+    for i in 0..len {
+        append_triangle(data, [center_index, corners[i], corners[(i + 1) % len]]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::chunks::resources::{
+        chunk::Chunk,
+        generator::{
+            noise::{hash, hash_to_unit},
+            sdf::SdfNode,
+            DensityGenerator,
+        },
+        mesh::{mesher::MesherKind, normals::NormalMode, MeshSettings},
+        voxel::Voxel,
+    };
+    use bevy::utils::HashMap;
+
+    /// Voxels inside of a box in the middle of the chunk follow "pattern", the rest are empty,
+    /// so the surface is closed
+    struct Pattern(fn(Position) -> bool);
+
+    impl DensityGenerator for Pattern {
+        fn get_voxel(&self, pos: Position) -> Voxel {
+            let inside = [pos.x, pos.y, pos.z]
+                .iter()
+                .all(|coordinate| (4..28).contains(coordinate));
+            Voxel {
+                value: if inside && (self.0)(pos) { 1. } else { -1. },
+                material: 0,
+            }
+        }
+    }
+
+    /// Every edge is used once in each direction, so triangles are consistently wound and
+    /// there are no holes or edges shared by more than 2 triangles
+    fn assert_watertight(generator: &dyn DensityGenerator) {
+        let chunk = Chunk::new(Position::new(0, 0, 0), generator);
+        let settings = MeshSettings {
+            mesher: MesherKind::AsymptoticMarchingCubes,
+            normal_mode: NormalMode::Gradient,
+            ..Default::default()
+        };
+        let data = chunk.generate_vertices(&settings, generator);
+        assert!(!data.indices.is_empty());
+
+        let mut edges: HashMap<(u32, u32), u32> = HashMap::default();
+        for triangle in data.indices.chunks_exact(3) {
+            for i in 0..3 {
+                let edge = (triangle[i], triangle[(i + 1) % 3]);
+                assert_ne!(edge.0, edge.1, "degenerate triangle");
+                *edges.entry(edge).or_insert(0) += 1;
+            }
+        }
+        for (&(a, b), &count) in edges.iter() {
+            assert_eq!(count, 1, "edge {:?} is used {} times", (a, b), count);
+            assert_eq!(
+                edges.get(&(b, a)),
+                Some(&1),
+                "edge {:?} has no twin",
+                (a, b)
+            );
+        }
+    }
+
+    #[test]
+    fn sphere_is_watertight() {
+        assert_watertight(&SdfNode::sphere(9.5).translate(Vec3::splat(16.)));
+    }
+
+    #[test]
+    fn checkerboard_is_watertight() {
+        // every face has diagonal corners filled, so all faces are ambiguous
+        assert_watertight(&Pattern(|pos| (pos.x + pos.y + pos.z) % 2 == 0));
+        // filled columns and slabs give saddles on some faces only
+        assert_watertight(&Pattern(|pos| (pos.x + pos.z) % 2 == 0));
+        assert_watertight(&Pattern(|pos| pos.x % 3 == 0 || pos.y % 2 == 0));
+    }
+
+    #[test]
+    fn random_voxels_are_watertight() {
+        // a mix of sparse, balanced and dense fills
+        assert_watertight(&Pattern(|pos| random(0, pos) < 0.2));
+        assert_watertight(&Pattern(|pos| random(1, pos) < 0.5));
+        assert_watertight(&Pattern(|pos| random(2, pos) < 0.8));
+    }
+
+    fn random(seed: u64, pos: Position) -> f32 {
+        hash_to_unit(hash(seed, pos.x as i32, pos.y as i32, pos.z as i32))
+    }
+}
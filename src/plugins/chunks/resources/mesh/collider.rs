@@ -0,0 +1,21 @@
+use super::MeshData;
+use bevy_rapier3d::prelude::Collider;
+
+/// Trimesh collider with the surface triangles of the mesh built from "data"
+///
+/// Skirts are left out, they hang below the surface and would only add invisible geometry.
+/// Returns None if there are no surface triangles.
+pub fn collider_from_data(data: &MeshData) -> Option<Collider> {
+    let (vertices, indices) = data.get_surface();
+    if indices.is_empty() {
+        return None;
+    }
+
+    let vertices = vertices.iter().map(|vertex| vertex.pos).collect();
+    let indices = indices
+        .chunks_exact(3)
+        .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+        .collect();
+
+    Some(Collider::trimesh(vertices, indices))
+}
@@ -0,0 +1,111 @@
+use super::{
+    append_vertices::{append_triangle, get_transition},
+    blend_material_weights,
+    edge_midpoints::EDGE_CORNERS,
+    get_material_weights, MeshData, Vertex,
+};
+use crate::plugins::chunks::resources::{
+    chunk::CHUNK_REAL_SIZE, chunk_view::ChunkView, pos::Position,
+};
+use bevy::math::Vec3;
+
+/// Mesh surface with a vertex inside each cell crossed by the surface
+///
+/// "place_vertex" gets min corner of the cell and in-chunk positions where the surface crosses
+/// cell edges, and returns position of the vertex.
+/// Each chunk owns edges starting at its voxels, so cells one stride before the chunk are used
+/// and meshes of neighbour chunks connect without gaps.
+pub fn generate_dual(
+    view: &ChunkView,
+    stride: i64,
+    iso_level: f32,
+    data: &mut MeshData,
+    place_vertex: impl Fn(Vec3, &[Vec3]) -> Vec3,
+) {
+    let is_filled = |pos: Position| view.get_voxel(pos).is_filled(iso_level);
+
+    // cells from -stride to CHUNK_REAL_SIZE - stride along each axis
+    let cells = CHUNK_REAL_SIZE as i64 / stride + 1;
+    let get_cell_index = |cell: Position| {
+        let cell = Position::new(cell.x + stride, cell.y + stride, cell.z + stride);
+        (cell.x / stride + cell.y / stride * cells + cell.z / stride * cells * cells) as usize
+    };
+
+    let mut cell_vertices = vec![u32::MAX; (cells * cells * cells) as usize];
+    let mut crossings = Vec::with_capacity(12);
+    for x in -1..cells - 1 {
+        for y in -1..cells - 1 {
+            for z in -1..cells - 1 {
+                let cell = Position::new(x, y, z) * stride;
+
+                crossings.clear();
+                for [a, b] in EDGE_CORNERS {
+                    let [a, b] = [a, b].map(|corner| {
+                        cell + Position::new(corner[0] as i64, corner[1] as i64, corner[2] as i64)
+                            * stride
+                    });
+                    let (voxel_a, voxel_b) = (view.get_voxel(a), view.get_voxel(b));
+                    if voxel_a.is_filled(iso_level) == voxel_b.is_filled(iso_level) {
+                        continue;
+                    }
+
+                    let transition = get_transition(voxel_a.value, voxel_b.value, iso_level);
+                    crossings.push(a.to_vec().lerp(b.to_vec(), transition));
+                }
+
+                if crossings.is_empty() {
+                    continue;
+                }
+
+                // blend materials of filled corners, empty voxels have no material
+                let materials = blend_material_weights((0..8).filter_map(|corner| {
+                    let offset = Position::new(corner & 1, (corner >> 1) & 1, corner >> 2);
+                    let voxel = view.get_voxel(cell + offset * stride);
+                    voxel
+                        .is_filled(iso_level)
+                        .then(|| get_material_weights(voxel.material))
+                }));
+
+                cell_vertices[get_cell_index(cell)] = data.vertices.len() as u32;
+                data.vertices.push(Vertex {
+                    pos: place_vertex(cell.to_vec(), &crossings),
+                    normal: Vec3::ZERO,
+                    materials,
+                });
+            }
+        }
+    }
+
+    // quad around each crossed edge owned by the chunk
+    for x in (0..CHUNK_REAL_SIZE as i64).step_by(stride as usize) {
+        for y in (0..CHUNK_REAL_SIZE as i64).step_by(stride as usize) {
+            for z in (0..CHUNK_REAL_SIZE as i64).step_by(stride as usize) {
+                let pos = Position::new(x, y, z);
+                for axis in 0..3 {
+                    let [axis_offset, u, v] = [axis, (axis + 1) % 3, (axis + 2) % 3].map(|i| {
+                        let mut offset = [0; 3];
+                        offset[i] = stride;
+                        Position::new(offset[0], offset[1], offset[2])
+                    });
+
+                    let filled = is_filled(pos);
+                    if filled == is_filled(pos + axis_offset) {
+                        continue;
+                    }
+
+                    let corners = [pos - u - v, pos - v, pos, pos - u]
+                        .map(|cell| cell_vertices[get_cell_index(cell)]);
+
+                    // faces must point towards lower density
+                    if filled {
+                        append_triangle(data, [corners[0], corners[2], corners[1]]);
+                        append_triangle(data, [corners[0], corners[3], corners[2]]);
+                    } else {
+                        append_triangle(data, [corners[0], corners[1], corners[2]]);
+                        append_triangle(data, [corners[0], corners[2], corners[3]]);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use super::{dual::generate_dual, mesher::Mesher, normals::get_gradient, MeshData};
+use crate::plugins::chunks::resources::{chunk_view::ChunkView, pos::Position};
+use bevy::math::{Mat3, Vec3};
+
+/// Dual Contouring, vertex of each cell minimizes distance to planes through edge crossings
+///
+/// Plane normals are taken from density gradient. The quadratic error function is solved with
+/// a small pull to the mass point of crossings, so flat areas don't get degenerate vertices.
+pub struct DualContouring;
+
+impl DualContouring {
+    /// Weight of the mass point in the quadratic error function
+    const MASS_POINT_WEIGHT: f32 = 0.05;
+
+    fn place_vertex(view: &ChunkView, stride: i64, cell: Vec3, crossings: &[Vec3]) -> Vec3 {
+        let sample = |pos: Position| view.get_voxel(pos).value;
+        let mass_point = crossings.iter().sum::<Vec3>() / crossings.len() as f32;
+
+        // normal equations of least squares, solved relative to the mass point
+        let mut ata = Mat3::from_diagonal(Vec3::splat(Self::MASS_POINT_WEIGHT));
+        let mut atb = Vec3::ZERO;
+        for crossing in crossings {
+            let normal = get_gradient(&sample, *crossing).normalize_or_zero();
+            ata += Mat3::from_cols(normal * normal.x, normal * normal.y, normal * normal.z);
+            atb += normal * normal.dot(*crossing - mass_point);
+        }
+
+        let pos = if ata.determinant().abs() > f32::EPSILON {
+            mass_point + ata.inverse() * atb
+        } else {
+            mass_point
+        };
+
+        // vertex must stay in its cell, otherwise the mesh can fold
+        pos.clamp(cell, cell + Vec3::splat(stride as f32))
+    }
+}
+
+impl Mesher for DualContouring {
+    fn generate(&self, view: &ChunkView, stride: i64, iso_level: f32, data: &mut MeshData) {
+        generate_dual(view, stride, iso_level, data, |cell, crossings| {
+            Self::place_vertex(view, stride, cell, crossings)
+        });
+    }
+
+    fn get_border_margin(&self, stride: i64) -> f32 {
+        stride as f32
+    }
+}
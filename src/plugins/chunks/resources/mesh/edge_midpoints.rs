@@ -34,3 +34,23 @@ pub enum EdgeMidpointsIndices {
 }
 
 pub const CUBE_EDGES_COUNT: usize = 12;
+
+/// Pair of cube corners (as voxel offsets) connected by each edge in order of [`EdgeMidpointsIndices`]  
+/// The first corner is always the one with lower coordinates
+pub const EDGE_CORNERS: [[[usize; 3]; 2]; CUBE_EDGES_COUNT] = [
+    // bottom cube edges
+    [[0, 0, 1], [1, 0, 1]],
+    [[1, 0, 0], [1, 0, 1]],
+    [[0, 0, 0], [1, 0, 0]],
+    [[0, 0, 0], [0, 0, 1]],
+    // top cube edges
+    [[0, 1, 1], [1, 1, 1]],
+    [[1, 1, 0], [1, 1, 1]],
+    [[0, 1, 0], [1, 1, 0]],
+    [[0, 1, 0], [0, 1, 1]],
+    // middle cube edges
+    [[0, 0, 1], [0, 1, 1]],
+    [[1, 0, 1], [1, 1, 1]],
+    [[1, 0, 0], [1, 1, 0]],
+    [[0, 0, 0], [0, 1, 0]],
+];
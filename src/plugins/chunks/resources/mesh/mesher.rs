@@ -0,0 +1,188 @@
+use super::{
+    append_vertices::{append_vertices, EdgeVertexCache},
+    asymptotic_decider::AsymptoticMarchingCubes,
+    dual_contouring::DualContouring,
+    surface_nets::SurfaceNets,
+    MeshData,
+};
+use crate::plugins::chunks::resources::{
+    chunk::CHUNK_REAL_SIZE, chunk_view::ChunkView, pos::Position,
+};
+
+/// Algorithm extracting isosurface from voxels of a chunk
+///
+/// Vertices are appended in in-chunk coordinates, shared between triangles, with not normalized
+/// sum of adjacent face normals. Face normals point towards lower density and triangles are
+/// wound counter-clockwise around them.
+pub trait Mesher: Send + Sync {
+    /// Append surface at "iso_level" of chunk cells, each cell is "stride" voxels wide
+    fn generate(&self, view: &ChunkView, stride: i64, iso_level: f32, data: &mut MeshData);
+
+    /// Max distance from chunk face to mesh border, used to find border edges for skirts
+    fn get_border_margin(&self, _stride: i64) -> f32 {
+        0.
+    }
+}
+
+/// Mesher used for the world, set in [`MeshSettings`]
+///
+/// [`MeshSettings`]: super::MeshSettings
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MesherKind {
+    /// Classic marching cubes based on triangulation table
+    #[default]
+    MarchingCubes,
+    /// Marching cubes with ambiguous faces resolved, watertight and manifold
+    AsymptoticMarchingCubes,
+    /// Vertex per cell at the average of edge crossings, smooth with fewer triangles
+    SurfaceNets,
+    /// Vertex per cell from Hermite data, keeps sharp features
+    DualContouring,
+}
+
+impl MesherKind {
+    pub fn get_mesher(&self) -> &'static dyn Mesher {
+        match self {
+            Self::MarchingCubes => &MarchingCubes,
+            Self::AsymptoticMarchingCubes => &AsymptoticMarchingCubes,
+            Self::SurfaceNets => &SurfaceNets,
+            Self::DualContouring => &DualContouring,
+        }
+    }
+}
+
+pub struct MarchingCubes;
+
+impl Mesher for MarchingCubes {
+    fn generate(&self, view: &ChunkView, stride: i64, iso_level: f32, data: &mut MeshData) {
+        let mut cache = EdgeVertexCache::new();
+        for x in (0..CHUNK_REAL_SIZE).step_by(stride as usize) {
+            for y in (0..CHUNK_REAL_SIZE).step_by(stride as usize) {
+                for z in (0..CHUNK_REAL_SIZE).step_by(stride as usize) {
+                    append_vertices(
+                        Position::new(x as i64, y as i64, z as i64),
+                        stride,
+                        iso_level,
+                        view,
+                        data,
+                        &mut cache,
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::chunks::resources::{
+        chunk::Chunk,
+        generator::{sdf::SdfNode, DensityGenerator},
+        mesh::MeshSettings,
+        voxel::Voxel,
+    };
+    use bevy::math::Vec3;
+
+    const MESHERS: [MesherKind; 4] = [
+        MesherKind::MarchingCubes,
+        MesherKind::AsymptoticMarchingCubes,
+        MesherKind::SurfaceNets,
+        MesherKind::DualContouring,
+    ];
+
+    /// horizontal plane, voxels at y = 16 are exactly zero
+    struct Plane;
+
+    impl DensityGenerator for Plane {
+        fn get_voxel(&self, pos: Position) -> Voxel {
+            Voxel {
+                value: 16. - pos.y as f32,
+                material: 0,
+            }
+        }
+    }
+
+    struct Constant(f32);
+
+    impl DensityGenerator for Constant {
+        fn get_voxel(&self, _: Position) -> Voxel {
+            Voxel {
+                value: self.0,
+                material: 0,
+            }
+        }
+    }
+
+    #[test]
+    fn surface_is_at_iso_level() {
+        let chunk = Chunk::new(Position::new(0, 0, 0), &Plane);
+        for mesher in MESHERS {
+            for iso_level in [0., 0.5, -3.25] {
+                let settings = MeshSettings {
+                    mesher,
+                    iso_level,
+                    ..Default::default()
+                };
+                let data = chunk.generate_vertices(&settings, &Plane);
+
+                assert!(!data.vertices.is_empty(), "{:?} at {}", mesher, iso_level);
+                for vertex in data.vertices.iter() {
+                    assert!(vertex.pos.is_finite() && vertex.normal.is_finite());
+                    assert!((vertex.pos.y - (16. - iso_level)).abs() < 1e-4);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn voxels_at_iso_level_are_filled() {
+        // all voxels are filled, so there is no surface
+        let generator = Constant(0.5);
+        let chunk = Chunk::new(Position::new(0, 0, 0), &generator);
+        for mesher in MESHERS {
+            let settings = MeshSettings {
+                mesher,
+                iso_level: 0.5,
+                ..Default::default()
+            };
+            assert!(chunk
+                .generate_vertices(&settings, &generator)
+                .vertices
+                .is_empty());
+        }
+    }
+
+    #[test]
+    fn triangles_face_empty_side() {
+        let center = Vec3::splat(16.);
+        let sphere = SdfNode::sphere(10.).translate(center);
+        let chunk = Chunk::new(Position::new(0, 0, 0), &sphere);
+
+        for mesher in MESHERS {
+            let settings = MeshSettings {
+                mesher,
+                ..Default::default()
+            };
+            let data = chunk.generate_vertices(&settings, &sphere);
+            assert!(!data.indices.is_empty());
+
+            for triangle in data.indices.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| data.vertices[triangle[i] as usize]);
+                // front face of counter-clockwise triangle
+                let front = (b.pos - a.pos).cross(c.pos - a.pos);
+                if front.length() < 1e-6 {
+                    continue;
+                }
+
+                let outwards = (a.pos + b.pos + c.pos) / 3. - center;
+                assert!(front.dot(outwards) > 0., "{:?}", mesher);
+                assert!(
+                    front.dot(a.normal + b.normal + c.normal) > 0.,
+                    "{:?}",
+                    mesher
+                );
+            }
+        }
+    }
+}
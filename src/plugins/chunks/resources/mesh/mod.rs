@@ -1,43 +1,162 @@
-use super::voxel::Voxel;
+use self::{mesher::MesherKind, normals::NormalMode};
+use super::voxel::{Voxel, DENSITY_LIMIT, MAX_MATERIALS};
 use bevy::{
     math::Vec3,
-    prelude::{Color, Mesh},
-    render::mesh::{self, PrimitiveTopology},
+    prelude::Mesh,
+    render::{
+        mesh::{self, MeshVertexAttribute, PrimitiveTopology},
+        render_resource::VertexFormat,
+    },
 };
 pub mod append_vertices;
+pub mod asymptotic_decider;
+#[cfg(feature = "rapier")]
+pub mod collider;
+mod dual;
+pub mod dual_contouring;
 pub mod edge_midpoints;
+pub mod mesher;
+pub mod normals;
+pub mod palette;
+pub mod skirts;
+pub mod surface_nets;
 pub mod triangulation_table;
 
+/// Weight of each material at vertex, weights sum up to 1
+pub type MaterialWeights = [f32; MAX_MATERIALS];
+
+/// Material weights of the first 4 materials
+pub const ATTRIBUTE_MATERIAL_WEIGHTS_0: MeshVertexAttribute = MeshVertexAttribute::new(
+    "Vertex_MaterialWeights0",
+    0x6d61_7400,
+    VertexFormat::Float32x4,
+);
+/// Material weights of materials from 4 to 8
+pub const ATTRIBUTE_MATERIAL_WEIGHTS_1: MeshVertexAttribute = MeshVertexAttribute::new(
+    "Vertex_MaterialWeights1",
+    0x6d61_7401,
+    VertexFormat::Float32x4,
+);
+
+#[derive(Clone, Copy)]
 pub struct Vertex {
     pub pos: Vec3,
     pub normal: Vec3,
-    pub color: Color,
+    pub materials: MaterialWeights,
 }
 
-pub type BlockOfVoxels = [[[Voxel; 2]; 2]; 2];
+/// Weights of a single material
+pub fn get_material_weights(material: u8) -> MaterialWeights {
+    let mut weights = [0.; MAX_MATERIALS];
+    weights[(material as usize).min(MAX_MATERIALS - 1)] = 1.;
+    weights
+}
+
+/// Average weights of all "materials"
+pub fn blend_material_weights(materials: impl Iterator<Item = MaterialWeights>) -> MaterialWeights {
+    let mut result = [0.; MAX_MATERIALS];
+    let mut count = 0;
+    for weights in materials {
+        result
+            .iter_mut()
+            .zip(weights)
+            .for_each(|(sum, weight)| *sum += weight);
+        count += 1;
+    }
 
-pub fn mesh_rom_vertices(vertices: Vec<Vertex>) -> Mesh {
-    let mut indices = Vec::new();
+    if count > 0 {
+        result.iter_mut().for_each(|weight| *weight /= count as f32);
+    }
+    result
+}
+
+/// Vertices shared between triangles and indices of triangle corners
+#[derive(Default)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    /// first vertex and index of skirts, they are always appended after the surface
+    pub skirts_start: Option<(usize, usize)>,
+}
 
+impl MeshData {
+    /// Vertices and indices of the surface without skirts
+    pub fn get_surface(&self) -> (&[Vertex], &[u32]) {
+        match self.skirts_start {
+            Some((vertex, index)) => (&self.vertices[..vertex], &self.indices[..index]),
+            None => (&self.vertices, &self.indices),
+        }
+    }
+}
+
+/// World-wide options of chunk meshing
+///
+/// # Example
+/// ```
+/// use marching_cubes::plugins::chunks::resources::mesh::{mesher::MesherKind, MeshSettings};
+///
+/// let settings = MeshSettings {
+///     mesher: MesherKind::SurfaceNets,
+///     iso_level: 0.5,
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MeshSettings {
+    /// can be overridden for a single chunk with [`Chunk::set_normal_mode`]
+    ///
+    /// [`Chunk::set_normal_mode`]: super::chunk::Chunk::set_normal_mode
+    pub normal_mode: NormalMode,
+    pub mesher: MesherKind,
+    /// density of the surface, voxels with not lower density are filled
+    ///
+    /// Settings setters clamp it to [`DENSITY_LIMIT`], stored densities never go beyond it.
+    pub iso_level: f32,
+}
+
+impl MeshSettings {
+    /// Settings with iso level clamped to [`DENSITY_LIMIT`], NaN is replaced with zero
+    pub fn clamped(self) -> Self {
+        let iso_level = if self.iso_level.is_nan() {
+            0.
+        } else {
+            self.iso_level.clamp(-DENSITY_LIMIT, DENSITY_LIMIT)
+        };
+
+        Self { iso_level, ..self }
+    }
+}
+
+pub type BlockOfVoxels = [[[Voxel; 2]; 2]; 2];
+
+/// Build mesh with material weights attributes for [`TerrainMaterial`]
+///
+/// [`TerrainMaterial`]: super::material::terrain::TerrainMaterial
+pub fn mesh_from_data(data: MeshData) -> Mesh {
     let mut positions: Vec<[f32; 3]> = Vec::new();
     let mut normals: Vec<[f32; 3]> = Vec::new();
-    let mut colors: Vec<u32> = Vec::new();
     let mut uvs: Vec<[f32; 2]> = Vec::new();
-    for vertex in vertices.iter() {
-        indices.push(positions.len() as u32);
-
+    let mut weights_0: Vec<[f32; 4]> = Vec::new();
+    let mut weights_1: Vec<[f32; 4]> = Vec::new();
+    for vertex in data.vertices.iter() {
         positions.push(vertex.pos.into());
         normals.push(vertex.normal.into());
-        colors.push(vertex.color.as_rgba().as_rgba_u32());
         uvs.push([0., 0.]);
+
+        let [a, b, c, d, e, f, g, h] = vertex.materials;
+        weights_0.push([a, b, c, d]);
+        weights_1.push([e, f, g, h]);
     }
 
     let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
-    mesh.set_indices(Some(mesh::Indices::U32(indices)));
+    mesh.set_indices(Some(mesh::Indices::U32(data.indices)));
     mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
     mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    // not used by the shader, but bevy's mesh pipeline requires uvs before the material
+    // specializes the vertex layout
     mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
-    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.insert_attribute(ATTRIBUTE_MATERIAL_WEIGHTS_0, weights_0);
+    mesh.insert_attribute(ATTRIBUTE_MATERIAL_WEIGHTS_1, weights_1);
 
     mesh
 }
@@ -0,0 +1,71 @@
+use super::{MeshData, Vertex};
+use crate::plugins::chunks::resources::pos::Position;
+use bevy::math::Vec3;
+
+/// How vertex normals of chunk mesh are computed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NormalMode {
+    /// Each triangle has its own vertices with face normal, gives faceted look
+    Flat,
+    /// Shared vertices with normal averaged from adjacent triangles
+    #[default]
+    Averaged,
+    /// Shared vertices with normal from density gradient, smooth across chunk borders
+    Gradient,
+}
+
+/// Density gradient at voxel "pos" from central differences
+fn get_voxel_gradient(sample: &impl Fn(Position) -> f32, pos: Position) -> Vec3 {
+    let axis_difference = |offset: Position| (sample(pos + offset) - sample(pos - offset)) / 2.;
+
+    Vec3::new(
+        axis_difference(Position::new(1, 0, 0)),
+        axis_difference(Position::new(0, 1, 0)),
+        axis_difference(Position::new(0, 0, 1)),
+    )
+}
+
+/// Density gradient at point "p" trilinearly interpolated from gradients of surrounding voxels
+pub fn get_gradient(sample: &impl Fn(Position) -> f32, p: Vec3) -> Vec3 {
+    let base = Position::from_vec(p);
+    let t = p - base.to_vec();
+
+    let mut result = Vec3::ZERO;
+    for x in 0..2 {
+        for y in 0..2 {
+            for z in 0..2 {
+                let weight = if x == 0 { 1. - t.x } else { t.x }
+                    * if y == 0 { 1. - t.y } else { t.y }
+                    * if z == 0 { 1. - t.z } else { t.z };
+                if weight > 0. {
+                    result += get_voxel_gradient(sample, base + Position::new(x, y, z)) * weight;
+                }
+            }
+        }
+    }
+
+    result
+}
+
+/// Split shared vertices, so each triangle gets its own vertices with face normal
+pub fn into_flat(data: MeshData) -> MeshData {
+    let mut result = MeshData {
+        // triangles keep their order, so each index gets its own vertex
+        skirts_start: data.skirts_start.map(|(_, index)| (index, index)),
+        ..Default::default()
+    };
+
+    for triangle in data.indices.chunks_exact(3) {
+        let [a, b, c] =
+            [triangle[0], triangle[1], triangle[2]].map(|index| data.vertices[index as usize]);
+        // triangles are counter-clockwise
+        let normal = (b.pos - a.pos).cross(c.pos - a.pos).normalize_or_zero();
+
+        for vertex in [a, b, c] {
+            result.indices.push(result.vertices.len() as u32);
+            result.vertices.push(Vertex { normal, ..vertex });
+        }
+    }
+
+    result
+}
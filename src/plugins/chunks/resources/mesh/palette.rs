@@ -0,0 +1,32 @@
+use crate::plugins::chunks::resources::voxel::MAX_MATERIALS;
+use bevy::prelude::Color;
+
+/// Color of each voxel material, used for untextured [`TerrainMaterial`]
+///
+/// [`TerrainMaterial`]: crate::plugins::chunks::resources::material::terrain::TerrainMaterial
+#[derive(Debug, Clone, Copy)]
+pub struct MaterialPalette {
+    pub colors: [Color; MAX_MATERIALS],
+}
+
+impl Default for MaterialPalette {
+    fn default() -> Self {
+        Self {
+            colors: [
+                // dirt
+                Color::rgb(0.5, 0.45, 0.4),
+                // rock
+                Color::rgb(0.45, 0.45, 0.47),
+                // sand
+                Color::rgb(0.85, 0.78, 0.55),
+                // snow
+                Color::rgb(0.95, 0.95, 0.97),
+                // grass
+                Color::rgb(0.3, 0.55, 0.2),
+                Color::rgb(0.6, 0.35, 0.25),
+                Color::rgb(0.35, 0.3, 0.25),
+                Color::rgb(0.7, 0.85, 0.9),
+            ],
+        }
+    }
+}
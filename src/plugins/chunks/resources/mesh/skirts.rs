@@ -0,0 +1,82 @@
+use super::{MeshData, Vertex};
+use bevy::utils::HashMap;
+
+/// Max distance of vertex from the face plane to be considered lying on it, added to margin
+const FACE_EPSILON: f32 = 1e-3;
+
+/// Face of chunk with a skirt
+#[derive(Clone)]
+pub struct SkirtFace {
+    /// 0 for X, 1 for Y and 2 for Z
+    pub axis: usize,
+    /// in-chunk coordinate of the face along the axis
+    pub coordinate: f32,
+    pub depth: f32,
+}
+
+/// Hide cracks between chunks meshed at different levels of detail
+///
+/// Each mesh border edge not farther than "margin" from one of "faces" gets a two-sided strip hanging from it
+/// towards the filled side of the surface. Vertices must have normalized normals.
+pub fn append_skirts(data: &mut MeshData, faces: &[SkirtFace], margin: f32) {
+    if faces.is_empty() {
+        return;
+    }
+    data.skirts_start = Some((data.vertices.len(), data.indices.len()));
+
+    // border edges are used by a single triangle
+    let mut edges_usage: HashMap<(u32, u32), u32> = HashMap::default();
+    for triangle in data.indices.chunks_exact(3) {
+        for (a, b) in triangle_edges(triangle) {
+            *edges_usage.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+        }
+    }
+
+    let mut skirt_vertices: HashMap<u32, u32> = HashMap::default();
+    let mut skirt_indices: Vec<u32> = Vec::new();
+    let triangles: Vec<u32> = data.indices.clone();
+
+    for triangle in triangles.chunks_exact(3) {
+        for (a, b) in triangle_edges(triangle) {
+            if edges_usage[&(a.min(b), a.max(b))] != 1 {
+                continue;
+            }
+
+            let (pos_a, pos_b) = (data.vertices[a as usize].pos, data.vertices[b as usize].pos);
+            let face = faces.iter().find(|face| {
+                (pos_a[face.axis] - face.coordinate).abs() < margin + FACE_EPSILON
+                    && (pos_b[face.axis] - face.coordinate).abs() < margin + FACE_EPSILON
+            });
+            let face = match face {
+                Some(face) => face,
+                None => continue,
+            };
+
+            let [skirt_a, skirt_b] = [a, b].map(|index| {
+                *skirt_vertices.entry(index).or_insert_with(|| {
+                    let vertex = data.vertices[index as usize];
+                    data.vertices.push(Vertex {
+                        // normals point towards the empty side
+                        pos: vertex.pos - vertex.normal * face.depth,
+                        ..vertex
+                    });
+                    data.vertices.len() as u32 - 1
+                })
+            });
+
+            // both windings, so the skirt is visible from any side
+            skirt_indices.extend([a, b, skirt_b, a, skirt_b, skirt_a]);
+            skirt_indices.extend([a, skirt_b, b, a, skirt_a, skirt_b]);
+        }
+    }
+
+    data.indices.extend(skirt_indices);
+}
+
+fn triangle_edges(triangle: &[u32]) -> [(u32, u32); 3] {
+    [
+        (triangle[0], triangle[1]),
+        (triangle[1], triangle[2]),
+        (triangle[2], triangle[0]),
+    ]
+}
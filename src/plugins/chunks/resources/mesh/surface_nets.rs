@@ -0,0 +1,18 @@
+use super::{dual::generate_dual, mesher::Mesher, MeshData};
+use crate::plugins::chunks::resources::chunk_view::ChunkView;
+use bevy::math::Vec3;
+
+/// Naive Surface Nets, vertex of each cell is the average of its edge crossings
+pub struct SurfaceNets;
+
+impl Mesher for SurfaceNets {
+    fn generate(&self, view: &ChunkView, stride: i64, iso_level: f32, data: &mut MeshData) {
+        generate_dual(view, stride, iso_level, data, |_, crossings| {
+            crossings.iter().sum::<Vec3>() / crossings.len() as f32
+        });
+    }
+
+    fn get_border_margin(&self, stride: i64) -> f32 {
+        stride as f32
+    }
+}
@@ -266,35 +266,38 @@ const TABLE: [[isize; 16]; 256] = [
     ],
 ];
 
-pub fn get_triangles_by_voxels(voxels: BlockOfVoxels) -> [isize; 16] {
+/// Triangles for cube of voxels, voxels are filled if [`Voxel::is_filled`] at "iso_level"
+///
+/// [`Voxel::is_filled`]: crate::plugins::chunks::resources::voxel::Voxel::is_filled
+pub fn get_triangles_by_voxels(voxels: BlockOfVoxels, iso_level: f32) -> [isize; 16] {
     // Since we have 256 options, we get exactly 8 bits per index.
     // As a result, we can use each voxel as a bit flag shifted by a certain number of bits
     let mut index: usize = 0;
 
-    if voxels[0][0][1].value >= 0. {
+    if voxels[0][0][1].is_filled(iso_level) {
         index |= 1 << 0;
     }
-    if voxels[1][0][1].value >= 0. {
+    if voxels[1][0][1].is_filled(iso_level) {
         // set the appropriate bit in the index for a specific non-empty voxel (second bit in this case)
         index |= 1 << 1;
     }
-    if voxels[1][0][0].value >= 0. {
+    if voxels[1][0][0].is_filled(iso_level) {
         index |= 1 << 2;
     }
-    if voxels[0][0][0].value >= 0. {
+    if voxels[0][0][0].is_filled(iso_level) {
         index |= 1 << 3;
     }
 
-    if voxels[0][1][1].value >= 0. {
+    if voxels[0][1][1].is_filled(iso_level) {
         index |= 1 << 4;
     }
-    if voxels[1][1][1].value >= 0. {
+    if voxels[1][1][1].is_filled(iso_level) {
         index |= 1 << 5;
     }
-    if voxels[1][1][0].value >= 0. {
+    if voxels[1][1][0].is_filled(iso_level) {
         index |= 1 << 6;
     }
-    if voxels[0][1][0].value >= 0. {
+    if voxels[0][1][0].is_filled(iso_level) {
         index |= 1 << 7;
     }
 
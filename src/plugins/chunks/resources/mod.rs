@@ -1,29 +1,118 @@
-use self::{chunk::Chunk, pos::Position};
-use std::slice::IterMut;
+use self::{
+    brush::{Brush, BrushOperation},
+    chunk::{Chunk, CHUNK_REAL_SIZE},
+    chunk_view::{ChunkNeighbours, ChunkView},
+    generator::DensityGenerator,
+    lod::MAX_LOD,
+    mesh::MeshSettings,
+    pos::Position,
+    raycast::RaycastHit,
+    region::RegionStorage,
+    voxel::Voxel,
+};
+use bevy::{
+    log::error,
+    math::Vec3,
+    tasks::TaskPool,
+    utils::{
+        hashbrown::hash_map::{Values, ValuesMut},
+        HashMap,
+    },
+};
+use std::{io, sync::Arc};
 
+pub mod brush;
 pub mod chunk;
+pub mod chunk_view;
+pub mod generator;
+pub mod lod;
+pub mod material;
 pub mod mesh;
+pub mod octree;
 pub mod pos;
+pub mod raycast;
+pub mod region;
+pub mod streaming;
+pub mod tasks;
 pub mod voxel;
+pub mod voxel_storage;
 
+/// Loaded chunks of the world, stored by chunk position
 pub struct ChunksHolder {
-    pub chunks: Vec<Option<Chunk>>,
+    chunks: HashMap<Position, Chunk>,
+    generator: Arc<dyn DensityGenerator>,
+    mesh_settings: MeshSettings,
+    /// storage of modified chunks, chunks are generated if it's not set
+    storage: Option<RegionStorage>,
 }
 
 impl ChunksHolder {
-    pub fn new(size: usize) -> Self {
+    /// Generate cube of "size" chunks centered at the origin, voxels of each chunk are generated
+    /// in parallel
+    pub fn new(size: usize, generator: Arc<dyn DensityGenerator>, pool: &TaskPool) -> Self {
         let volume = size * size * size;
-        let chunks = (0..volume)
+        let offset = size as i64 / 2;
+
+        let chunks: Vec<Chunk> = (0..volume)
             .map(|index| {
-                let offset = size as i64 / 2;
                 let chunk_pos =
                     Self::get_pos_by_index(size, index) - Position::new(offset, offset, offset);
-                let chunk = Chunk::new(chunk_pos);
-                Some(chunk)
+                Chunk::new_parallel(chunk_pos, generator.as_ref(), pool)
             })
             .collect();
 
-        Self { chunks }
+        Self {
+            chunks: chunks
+                .into_iter()
+                .map(|chunk| (chunk.get_pos(), chunk))
+                .collect(),
+            generator,
+            mesh_settings: MeshSettings::default(),
+            storage: None,
+        }
+    }
+
+    /// Load and save modified chunks with "storage", saved chunks replace generated ones
+    pub fn with_storage(mut self, storage: RegionStorage) -> Self {
+        for chunk in self.chunks.values_mut() {
+            match storage.load(chunk.get_pos()) {
+                Ok(Some(saved)) => *chunk = saved,
+                Ok(None) => {}
+                Err(err) => error!("failed to load chunk {:?}: {}", chunk.get_pos(), err),
+            }
+        }
+
+        self.storage = Some(storage);
+        self
+    }
+
+    pub fn get_storage(&self) -> Option<&RegionStorage> {
+        self.storage.as_ref()
+    }
+
+    /// Save all loaded chunks with unsaved changes, does nothing if there is no storage
+    pub fn save(&mut self) -> io::Result<()> {
+        if let Some(storage) = &self.storage {
+            storage.save(self.chunks.values())?;
+            self.iter_mut().for_each(|chunk| chunk.set_saved());
+        }
+        Ok(())
+    }
+
+    pub fn get_generator(&self) -> &Arc<dyn DensityGenerator> {
+        &self.generator
+    }
+
+    pub fn get_mesh_settings(&self) -> &MeshSettings {
+        &self.mesh_settings
+    }
+
+    /// Change meshing options and redraw all chunks, iso level is clamped to [`DENSITY_LIMIT`]
+    ///
+    /// [`DENSITY_LIMIT`]: voxel::DENSITY_LIMIT
+    pub fn set_mesh_settings(&mut self, settings: MeshSettings) {
+        self.mesh_settings = settings.clamped();
+        self.iter_mut().for_each(|chunk| chunk.set_need_update());
     }
 
     fn get_pos_by_index(size: usize, index: usize) -> Position {
@@ -34,7 +123,505 @@ impl ChunksHolder {
         )
     }
 
-    pub fn iter_chunks_mut(&mut self) -> IterMut<Option<Chunk>> {
-        self.chunks.iter_mut()
+    pub fn get(&self, pos: Position) -> Option<&Chunk> {
+        self.chunks.get(&pos)
+    }
+
+    pub fn get_mut(&mut self, pos: Position) -> Option<&mut Chunk> {
+        self.chunks.get_mut(&pos)
+    }
+
+    pub fn contains(&self, pos: Position) -> bool {
+        self.chunks.contains_key(&pos)
+    }
+
+    /// Insert chunk at its position, returns replaced chunk if there was one
+    ///
+    /// Face neighbours are marked for redraw, so they add skirts if the chunk has different level
+    /// of detail. All neighbours are marked if the chunk is modified, as they were meshed
+    /// against generated voxels.
+    pub fn insert(&mut self, chunk: Chunk) -> Option<Chunk> {
+        let (pos, is_modified) = (chunk.get_pos(), chunk.is_modified());
+        let replaced = self.chunks.insert(pos, chunk);
+
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let offset = Position::new(x, y, z);
+                    let distance = offset.length_squared();
+                    if distance == 0 || (distance > 1 && !is_modified) {
+                        continue;
+                    }
+                    if let Some(neighbour) = self.get_mut(pos + offset) {
+                        neighbour.set_need_update();
+                    }
+                }
+            }
+        }
+
+        replaced
+    }
+
+    /// Remove chunk from the world, the caller is responsible for despawning its entity
+    pub fn remove(&mut self, pos: Position) -> Option<Chunk> {
+        self.chunks.remove(&pos)
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = Position> + '_ {
+        self.chunks.keys().copied()
+    }
+
+    /// Position of chunk containing voxel at world position "pos"
+    pub fn get_chunk_pos(pos: Position) -> Position {
+        pos.div_floor(CHUNK_REAL_SIZE as i64)
+    }
+
+    pub fn get_voxel_world(&self, pos: Position) -> Option<Voxel> {
+        // voxels at chunk borders can be read from any loaded chunk storing them
+        Self::get_chunks_with_voxel(pos, 0).find_map(|chunk_pos| {
+            self.get(chunk_pos)
+                .map(|chunk| chunk.get_voxel(pos - chunk_pos * CHUNK_REAL_SIZE as i64))
+        })
+    }
+
+    /// Set voxel at world position "pos" and mark affected chunks for redraw
+    ///
+    /// Voxels at chunk borders are stored in both neighbouring chunks, so up to 8 chunks can be
+    /// updated. Neighbour chunks which see the voxel through their apron are redrawn too.
+    /// Returns false if none of the chunks storing the voxel is loaded.
+    pub fn set_voxel_world(&mut self, pos: Position, voxel: Voxel) -> bool {
+        let mut is_set = false;
+        for chunk_pos in Self::get_chunks_with_voxel(pos, 0) {
+            if let Some(chunk) = self.get_mut(chunk_pos) {
+                chunk.set_voxel(pos - chunk_pos * CHUNK_REAL_SIZE as i64, voxel);
+                is_set = true;
+            }
+        }
+
+        if is_set {
+            let max_apron = ChunkView::get_apron(MAX_LOD) as i64;
+            for chunk_pos in Self::get_chunks_with_voxel(pos, max_apron) {
+                if let Some(chunk) = self.get_mut(chunk_pos) {
+                    // apron is wider for chunks at lower level of detail
+                    let apron = ChunkView::get_apron(chunk.get_lod()) as i64;
+                    let local = pos - chunk_pos * CHUNK_REAL_SIZE as i64;
+                    let range = -apron..=CHUNK_REAL_SIZE as i64 + apron;
+                    if [local.x, local.y, local.z]
+                        .iter()
+                        .all(|coordinate| range.contains(coordinate))
+                    {
+                        chunk.set_need_update();
+                    }
+                }
+            }
+        }
+
+        is_set
+    }
+
+    /// Chunk at "pos" with all loaded neighbours, used to build [`ChunkView`]
+    ///
+    /// [`ChunkView`]: chunk_view::ChunkView
+    pub fn get_neighbours(&self, pos: Position) -> ChunkNeighbours {
+        ChunkNeighbours::new(pos, |pos| self.get(pos).cloned())
+    }
+
+    /// Modify voxels around world point "center", only chunks with changed voxels are redrawn
+    pub fn apply_brush(&mut self, center: Vec3, brush: &Brush) {
+        if !brush.shape.is_valid() || !center.is_finite() {
+            return;
+        }
+        // flatten plane is undefined
+        if let BrushOperation::Flatten { normal } = brush.operation {
+            if normal.normalize_or_zero() == Vec3::ZERO {
+                return;
+            }
+        }
+
+        let min = Position::from_vec(center - brush.shape.extents());
+        let max = Position::from_vec(center + brush.shape.extents()) + Position::new(1, 1, 1);
+
+        // compute all changes before applying them, so smoothing reads unmodified voxels
+        let mut changes: Vec<(Position, Voxel)> = Vec::new();
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    let pos = Position::new(x, y, z);
+                    let offset = pos.to_vec() - center;
+
+                    // negated comparison skips NaN weights too
+                    let weight = brush.get_weight(offset);
+                    if !(weight > 0. && weight.is_finite()) {
+                        continue;
+                    }
+
+                    let voxel = match self.get_voxel_world(pos) {
+                        Some(voxel) => voxel,
+                        None => continue,
+                    };
+
+                    let value = match brush.operation {
+                        BrushOperation::Add => voxel.value + weight,
+                        BrushOperation::Subtract => voxel.value - weight,
+                        BrushOperation::Smooth => {
+                            let average = self.get_neighbours_average(pos, voxel);
+                            voxel.value + (average - voxel.value) * weight.min(1.)
+                        }
+                        BrushOperation::Flatten { normal } => {
+                            let target = -offset.dot(normal.normalize_or_zero());
+                            voxel.value + (target - voxel.value) * weight.min(1.)
+                        }
+                        BrushOperation::Paint { .. } => voxel.value,
+                    };
+                    if !value.is_finite() {
+                        continue;
+                    }
+
+                    let material = match brush.operation {
+                        BrushOperation::Paint { material } => material,
+                        _ => voxel.material,
+                    };
+
+                    // voxels already at the limit or target don't need a redraw
+                    let changed = Voxel { value, material }.clamped();
+                    if changed != voxel {
+                        changes.push((pos, changed));
+                    }
+                }
+            }
+        }
+
+        for (pos, voxel) in changes {
+            self.set_voxel_world(pos, voxel);
+        }
+    }
+
+    /// Average density of 6 direct neighbours, missing voxels are replaced with "voxel"
+    fn get_neighbours_average(&self, pos: Position, voxel: Voxel) -> f32 {
+        let neighbours = [
+            Position::new(1, 0, 0),
+            Position::new(-1, 0, 0),
+            Position::new(0, 1, 0),
+            Position::new(0, -1, 0),
+            Position::new(0, 0, 1),
+            Position::new(0, 0, -1),
+        ];
+
+        neighbours
+            .iter()
+            .map(|&offset| self.get_voxel_world(pos + offset).unwrap_or(voxel).value)
+            .sum::<f32>()
+            / neighbours.len() as f32
+    }
+
+    /// Positions of all chunks which store voxel at world position "pos",
+    /// or have it within their "apron" sized padding
+    fn get_chunks_with_voxel(pos: Position, apron: i64) -> impl Iterator<Item = Position> {
+        let chunk_pos = Self::get_chunk_pos(pos);
+        let local = pos - chunk_pos * CHUNK_REAL_SIZE as i64;
+
+        // voxel with zero in-chunk coordinate is also the last voxel of the previous chunk
+        let size = CHUNK_REAL_SIZE as i64;
+        let range = move |local: i64| {
+            let from = if local <= apron { -1 } else { 0 };
+            let to = if local >= size - apron { 1 } else { 0 };
+            from..=to
+        };
+
+        range(local.x).flat_map(move |x| {
+            range(local.y)
+                .flat_map(move |y| range(local.z).map(move |z| chunk_pos + Position::new(x, y, z)))
+        })
+    }
+
+    /// First point where the ray crosses the surface at iso level of mesh settings
+    ///
+    /// Returns None if nothing is hit within "max_distance" or the ray leaves loaded chunks.
+    ///
+    /// ```
+    /// # use marching_cubes::plugins::chunks::resources::{
+    /// #     generator::DensityGenerator, pos::Position, voxel::Voxel, ChunksHolder,
+    /// # };
+    /// # use bevy::{math::Vec3, tasks::TaskPool};
+    /// # use std::sync::Arc;
+    /// // ground with surface at y = 16
+    /// struct Plane;
+    /// impl DensityGenerator for Plane {
+    ///     fn get_voxel(&self, pos: Position) -> Voxel {
+    ///         Voxel { value: 16. - pos.y as f32, material: 0 }
+    ///     }
+    /// }
+    ///
+    /// let chunks = ChunksHolder::new(2, Arc::new(Plane), &TaskPool::new());
+    /// let hit = chunks
+    ///     .raycast(Vec3::new(5.3, 30., 5.8), Vec3::new(0.2, -1., 0.1), 100.)
+    ///     .unwrap();
+    ///
+    /// assert!((hit.position.y - 16.).abs() < 1e-3);
+    /// assert!(hit.normal.abs_diff_eq(Vec3::Y, 1e-3));
+    /// assert_eq!(hit.voxel.y, 16);
+    /// assert_eq!(hit.chunk, Position::new(0, 0, 0));
+    ///
+    /// // pointing away from the ground
+    /// assert!(chunks.raycast(Vec3::new(5., 30., 5.), Vec3::Y, 100.).is_none());
+    /// ```
+    pub fn raycast(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<RaycastHit> {
+        raycast::raycast(self, origin, direction, max_distance)
+    }
+
+    pub fn iter(&self) -> Values<'_, Position, Chunk> {
+        self.chunks.values()
+    }
+
+    pub fn iter_mut(&mut self) -> ValuesMut<'_, Position, Chunk> {
+        self.chunks.values_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::chunks::resources::{brush::BrushShape, voxel::DENSITY_LIMIT};
+
+    /// Flat ground, filled below y = 0
+    struct Plane;
+
+    impl DensityGenerator for Plane {
+        fn get_voxel(&self, pos: Position) -> Voxel {
+            Voxel {
+                value: -pos.y as f32,
+                material: 0,
+            }
+        }
+    }
+
+    /// Chunks from -1 to 0 on each axis, none of them needs a redraw
+    fn new_chunks() -> ChunksHolder {
+        let mut chunks = ChunksHolder::new(2, Arc::new(Plane), &TaskPool::new());
+        chunks.iter_mut().for_each(|chunk| chunk.set_updated());
+        chunks
+    }
+
+    fn count_need_update(chunks: &ChunksHolder) -> usize {
+        chunks.iter().filter(|chunk| chunk.is_need_update()).count()
+    }
+
+    fn get_value(chunks: &ChunksHolder, x: i64, y: i64, z: i64) -> f32 {
+        chunks
+            .get_voxel_world(Position::new(x, y, z))
+            .expect("voxel is loaded")
+            .value
+    }
+
+    fn sphere(radius: f32, operation: BrushOperation) -> Brush {
+        Brush::new(BrushShape::Sphere { radius }, operation)
+    }
+
+    #[test]
+    fn voxels_at_negative_positions() {
+        let mut chunks = new_chunks();
+        let pos = Position::new(-1, -7, -33);
+        assert_eq!(ChunksHolder::get_chunk_pos(pos), Position::new(-1, -1, -2));
+        // chunk at -2 isn't loaded, but it shares the voxel at -32 with chunk at -1
+        assert!(chunks.get_voxel_world(pos).is_none());
+        assert!(!chunks.set_voxel_world(pos, Voxel::default()));
+
+        let pos = Position::new(-1, -7, -32);
+        assert_eq!(get_value(&chunks, -1, -7, -32), 7.);
+        assert!(chunks.set_voxel_world(pos, Voxel::default()));
+        assert_eq!(get_value(&chunks, -1, -7, -32), 0.);
+    }
+
+    #[test]
+    fn border_voxels_are_shared() {
+        let mut chunks = new_chunks();
+        let origin = Position::new(0, 0, 0);
+        let voxel = Voxel {
+            value: 3.,
+            material: 1,
+        };
+
+        // voxel at the corner of all 8 chunks is stored in each of them
+        chunks.set_voxel_world(origin, voxel);
+        for chunk in chunks.iter() {
+            let local = origin - chunk.get_pos() * CHUNK_REAL_SIZE as i64;
+            assert_eq!(chunk.get_voxel(local), voxel);
+            assert!(chunk.is_need_update());
+        }
+
+        // copies are read while the owner chunk is unloaded
+        chunks.remove(origin);
+        assert_eq!(chunks.get_voxel_world(origin), Some(voxel));
+    }
+
+    #[test]
+    fn apron_neighbours_are_redrawn() {
+        let mut chunks = new_chunks();
+        let neighbour = Position::new(-1, 0, 0);
+
+        // voxel 3 voxels from the border is outside of the apron at the highest detail
+        let pos = Position::new(3, 5, 5);
+        chunks.set_voxel_world(pos, Voxel::default());
+        assert!(chunks.get(Position::new(0, 0, 0)).unwrap().is_need_update());
+        assert_eq!(count_need_update(&chunks), 1);
+
+        // apron is wider at lower detail, so the neighbour reads the voxel too
+        chunks.iter_mut().for_each(|chunk| chunk.set_updated());
+        chunks.get_mut(neighbour).unwrap().set_lod(MAX_LOD);
+        chunks.get_mut(neighbour).unwrap().set_updated();
+        chunks.set_voxel_world(pos, Voxel::default());
+        assert!(chunks.get(neighbour).unwrap().is_need_update());
+        assert_eq!(count_need_update(&chunks), 2);
+
+        // voxel at the border is read by neighbours at any detail
+        chunks.iter_mut().for_each(|chunk| chunk.set_updated());
+        chunks.set_voxel_world(Position::new(1, 5, -1), Voxel::default());
+        assert_eq!(count_need_update(&chunks), 4);
+    }
+
+    #[test]
+    fn iso_level_is_clamped() {
+        let mut chunks = new_chunks();
+        for (iso_level, clamped) in [(0.5, 0.5), (40., DENSITY_LIMIT), (-1e9, -DENSITY_LIMIT)] {
+            chunks.set_mesh_settings(MeshSettings {
+                iso_level,
+                ..Default::default()
+            });
+            assert_eq!(chunks.get_mesh_settings().iso_level, clamped);
+        }
+
+        chunks.set_mesh_settings(MeshSettings {
+            iso_level: f32::NAN,
+            ..Default::default()
+        });
+        assert_eq!(chunks.get_mesh_settings().iso_level, 0.);
+    }
+
+    #[test]
+    fn inserted_chunks_redraw_neighbours() {
+        let mut chunks = new_chunks();
+        let pos = Position::new(0, 0, 0);
+
+        // generated chunk only changes skirts of face neighbours
+        chunks.remove(pos);
+        chunks.insert(Chunk::new(pos, &Plane));
+        let faces = [(-1, 0, 0), (0, -1, 0), (0, 0, -1)];
+        for chunk in chunks.iter().filter(|chunk| chunk.get_pos() != pos) {
+            let offset = chunk.get_pos();
+            let is_face = faces.contains(&(offset.x, offset.y, offset.z));
+            assert_eq!(chunk.is_need_update(), is_face, "{:?}", offset);
+        }
+
+        // modified chunk changes voxels seen by all neighbours
+        chunks.iter_mut().for_each(|chunk| chunk.set_updated());
+        let mut chunk = chunks.remove(pos).unwrap();
+        chunk.set_voxel(Position::new(0, 0, 0), Voxel::default());
+        chunks.insert(chunk);
+        assert_eq!(count_need_update(&chunks), 8);
+    }
+
+    #[test]
+    fn add_and_subtract() {
+        let mut chunks = new_chunks();
+        let brush = sphere(4., BrushOperation::Add).with_strength(2.);
+        chunks.apply_brush(Vec3::new(16., 0., 16.), &brush);
+
+        // falloff halves the strength at 3/4 of the radius
+        assert_eq!(get_value(&chunks, 16, 0, 16), 2.);
+        assert_eq!(get_value(&chunks, 16, 3, 16), -2.);
+        assert_eq!(get_value(&chunks, 16, -3, 16), 4.);
+        assert_eq!(get_value(&chunks, 20, 0, 16), 0.);
+        // ground voxels are stored in both upper and lower chunks
+        assert_eq!(count_need_update(&chunks), 2);
+
+        let brush = BrushOperation::Subtract;
+        chunks.apply_brush(
+            Vec3::new(16., 0., 16.),
+            &sphere(4., brush).with_strength(2.),
+        );
+        assert_eq!(get_value(&chunks, 16, 0, 16), 0.);
+        assert_eq!(get_value(&chunks, 16, 3, 16), -3.);
+    }
+
+    #[test]
+    fn densities_at_limit_are_not_changed() {
+        let mut chunks = new_chunks();
+
+        // voxels deep in the ground and high in the air are stored clamped
+        let brush = sphere(3., BrushOperation::Add).with_strength(5.);
+        chunks.apply_brush(Vec3::new(-10., -20., 10.), &brush);
+        let brush = sphere(3., BrushOperation::Subtract).with_strength(5.);
+        chunks.apply_brush(Vec3::new(-10., 20., 10.), &brush);
+
+        assert_eq!(get_value(&chunks, -10, -20, 10), DENSITY_LIMIT);
+        assert_eq!(get_value(&chunks, -10, 20, 10), -DENSITY_LIMIT);
+        assert_eq!(count_need_update(&chunks), 0);
+
+        // results are clamped too
+        let brush = sphere(3., BrushOperation::Add).with_strength(100.);
+        chunks.apply_brush(Vec3::new(-10., 0., 10.), &brush);
+        assert_eq!(get_value(&chunks, -10, 0, 10), DENSITY_LIMIT);
+    }
+
+    #[test]
+    fn paint_changes_only_material() {
+        let mut chunks = new_chunks();
+        let center = Position::new(16, -8, 16);
+
+        chunks.apply_brush(
+            center.to_vec(),
+            &sphere(2., BrushOperation::Paint { material: 0 }),
+        );
+        assert_eq!(count_need_update(&chunks), 0);
+
+        chunks.apply_brush(
+            center.to_vec(),
+            &sphere(2., BrushOperation::Paint { material: 2 }),
+        );
+        let voxel = chunks.get_voxel_world(center).unwrap();
+        assert_eq!(voxel.material, 2);
+        assert_eq!(voxel.value, 8.);
+        assert_eq!(count_need_update(&chunks), 1);
+    }
+
+    #[test]
+    fn smooth_and_flatten_stop_at_target() {
+        let mut chunks = new_chunks();
+        let center = Vec3::new(-16., 0., -16.);
+
+        // plane is already smooth and flat
+        chunks.apply_brush(center, &sphere(3., BrushOperation::Smooth));
+        chunks.apply_brush(
+            center,
+            &sphere(3., BrushOperation::Flatten { normal: Vec3::Y }),
+        );
+        assert_eq!(count_need_update(&chunks), 0);
+
+        // spike is blended with the average of its neighbours
+        let spike = Position::new(-16, 0, -16);
+        chunks.set_voxel_world(
+            spike,
+            Voxel {
+                value: 6.,
+                material: 0,
+            },
+        );
+        let brush = sphere(1., BrushOperation::Smooth).with_falloff(0.);
+        chunks.apply_brush(center, &brush);
+        assert_eq!(get_value(&chunks, -16, 0, -16), 0.);
+
+        // flatten moves the ground towards the plane through the brush center
+        let brush = sphere(3., BrushOperation::Flatten { normal: Vec3::Y }).with_falloff(0.);
+        chunks.apply_brush(Vec3::new(-16., 2., -16.), &brush);
+        assert_eq!(get_value(&chunks, -16, 0, -16), 2.);
+        assert_eq!(get_value(&chunks, -16, 3, -16), -1.);
     }
 }
@@ -0,0 +1,685 @@
+use super::{
+    chunk::{Chunk, CHUNK_REAL_SIZE},
+    chunk_view::{ChunkNeighbours, ChunkView},
+    generator::{DensityGenerator, ScaledGenerator},
+    mesh::{skirts::SkirtFace, MeshSettings},
+    pos::Position,
+};
+use bevy::{
+    math::{Mat4, Vec3},
+    prelude::Mesh,
+    render::primitives::{Aabb, Frustum},
+    tasks::TaskPool,
+};
+use std::sync::Arc;
+
+/// Options of [`ChunkOctree`] updates
+pub struct OctreeSettings {
+    /// level of root nodes, the world is 2^(max_level + 1) chunks along each axis,
+    /// only used when the octree is created
+    pub max_level: u8,
+    /// node is split when a loader is closer to it than "split_distance" of node sizes
+    pub split_distance: f32,
+    /// node is merged when all loaders are farther than "merge_distance" of node sizes,
+    /// bigger than "split_distance", so nodes near the threshold don't flip every frame
+    pub merge_distance: f32,
+    /// max number of node splits or merges being generated at once
+    pub update_budget: usize,
+}
+
+impl Default for OctreeSettings {
+    fn default() -> Self {
+        Self {
+            max_level: 6,
+            split_distance: 1.5,
+            merge_distance: 2.,
+            update_budget: 4,
+        }
+    }
+}
+
+enum NodeContent {
+    Leaf(Chunk),
+    /// children indexed by x + y * 2 + z * 4 of child offset
+    Branch(Vec<OctreeNode>),
+}
+
+/// Node of [`ChunkOctree`], covers cube of 2^level chunks along each axis
+///
+/// Leaves store a single chunk with voxels sampled every 2^level world voxels, so every leaf
+/// takes the same amount of memory regardless of its size.
+pub struct OctreeNode {
+    level: u8,
+    /// position in units of node size
+    pos: Position,
+    content: NodeContent,
+}
+
+impl OctreeNode {
+    fn new_leaf(level: u8, pos: Position, generator: &Arc<dyn DensityGenerator>) -> Self {
+        let generator = ScaledGenerator::new(generator.clone(), 1 << level);
+        Self {
+            level,
+            pos,
+            content: NodeContent::Leaf(Chunk::new(pos, &generator)),
+        }
+    }
+
+    pub fn get_level(&self) -> u8 {
+        self.level
+    }
+
+    pub fn get_pos(&self) -> Position {
+        self.pos
+    }
+
+    /// Number of world voxels per voxel of the node's chunk
+    pub fn get_scale(&self) -> i64 {
+        1 << self.level
+    }
+
+    /// Bounds of the node in world coordinates
+    pub fn get_aabb(&self) -> Aabb {
+        let size = (self.get_scale() * CHUNK_REAL_SIZE as i64) as f32;
+        let min = self.pos.to_vec() * size;
+        Aabb::from_min_max(min, min + Vec3::splat(size))
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        matches!(self.content, NodeContent::Leaf(_))
+    }
+
+    /// Chunk of the leaf, its positions are in units of the node's scale
+    pub fn get_chunk(&self) -> Option<&Chunk> {
+        match &self.content {
+            NodeContent::Leaf(chunk) => Some(chunk),
+            NodeContent::Branch(_) => None,
+        }
+    }
+
+    pub fn get_chunk_mut(&mut self) -> Option<&mut Chunk> {
+        match &mut self.content {
+            NodeContent::Leaf(chunk) => Some(chunk),
+            NodeContent::Branch(_) => None,
+        }
+    }
+
+    /// Check if node covers node at "level" and "pos"
+    fn contains(&self, level: u8, pos: Position) -> bool {
+        level <= self.level && pos.div_floor(1 << (self.level - level)) == self.pos
+    }
+
+    fn get_child_index(&self, level: u8, pos: Position) -> usize {
+        let offset = pos.div_floor(1 << (self.level - 1 - level)) - self.pos * 2;
+        (offset.x + offset.y * 2 + offset.z * 4) as usize
+    }
+
+    fn get_child_pos(pos: Position, index: usize) -> Position {
+        let offset = Position::new(
+            (index % 2) as i64,
+            ((index / 2) % 2) as i64,
+            (index / 4) as i64,
+        );
+        pos * 2 + offset
+    }
+
+    fn get_node_mut(&mut self, level: u8, pos: Position) -> &mut OctreeNode {
+        if self.level <= level || self.is_leaf() {
+            return self;
+        }
+
+        let index = self.get_child_index(level, pos);
+        match &mut self.content {
+            NodeContent::Branch(children) => children[index].get_node_mut(level, pos),
+            NodeContent::Leaf(_) => unreachable!(),
+        }
+    }
+
+    fn collect_leaves<'a>(&'a self, filter: &impl Fn(&Aabb) -> bool, leaves: &mut Vec<&'a Self>) {
+        if !filter(&self.get_aabb()) {
+            return;
+        }
+
+        match &self.content {
+            NodeContent::Leaf(_) => leaves.push(self),
+            NodeContent::Branch(children) => children
+                .iter()
+                .for_each(|child| child.collect_leaves(filter, leaves)),
+        }
+    }
+
+    fn collect_leaves_mut<'a>(
+        &'a mut self,
+        filter: &impl Fn(&Aabb) -> bool,
+        leaves: &mut Vec<&'a mut Self>,
+    ) {
+        if !filter(&self.get_aabb()) {
+            return;
+        }
+
+        if self.is_leaf() {
+            leaves.push(self);
+        } else if let NodeContent::Branch(children) = &mut self.content {
+            children
+                .iter_mut()
+                .for_each(|child| child.collect_leaves_mut(filter, leaves));
+        }
+    }
+
+    /// Take all leaves, used to despawn entities of merged nodes
+    fn into_leaves(self, leaves: &mut Vec<OctreeNode>) {
+        match self.content {
+            NodeContent::Leaf(_) => leaves.push(self),
+            NodeContent::Branch(children) => children
+                .into_iter()
+                .for_each(|child| child.into_leaves(leaves)),
+        }
+    }
+}
+
+/// Everything needed to mesh an octree leaf, see [`ChunkOctree::get_mesh_job`]
+pub struct LeafMeshJob {
+    chunk: Chunk,
+    faces: Vec<SkirtFace>,
+    generator: ScaledGenerator,
+    settings: MeshSettings,
+}
+
+impl LeafMeshJob {
+    /// Mesh of the leaf, vertices are in units of the leaf's scale
+    pub fn generate_mesh(self) -> Mesh {
+        let pos = self.chunk.get_pos();
+        let chunk = self.chunk;
+        let neighbours =
+            ChunkNeighbours::new(pos, |neighbour| (neighbour == pos).then(|| chunk.clone()));
+        ChunkView::new(&neighbours, &self.generator)
+            .with_skirt_faces(self.faces)
+            .generate_mesh(&self.settings)
+    }
+}
+
+/// Change of the octree structure, see [`ChunkOctree::get_updates`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OctreeUpdate {
+    /// replace leaf with 8 children
+    Split { level: u8, pos: Position },
+    /// replace branch with a single leaf
+    Merge { level: u8, pos: Position },
+}
+
+impl OctreeUpdate {
+    /// Level and position of the changed node
+    pub fn get_node(&self) -> (u8, Position) {
+        match *self {
+            Self::Split { level, pos } | Self::Merge { level, pos } => (level, pos),
+        }
+    }
+}
+
+/// Everything needed to generate chunks of an [`OctreeUpdate`] on another thread
+pub struct OctreeUpdateJob {
+    update: OctreeUpdate,
+    generator: Arc<dyn DensityGenerator>,
+}
+
+impl OctreeUpdateJob {
+    pub fn generate(self) -> GeneratedUpdate {
+        let content = match self.update {
+            OctreeUpdate::Split { level, pos } => NodeContent::Branch(
+                (0..8)
+                    .map(|index| {
+                        let child_pos = OctreeNode::get_child_pos(pos, index);
+                        OctreeNode::new_leaf(level - 1, child_pos, &self.generator)
+                    })
+                    .collect(),
+            ),
+            OctreeUpdate::Merge { level, pos } => {
+                OctreeNode::new_leaf(level, pos, &self.generator).content
+            }
+        };
+
+        GeneratedUpdate {
+            update: self.update,
+            content,
+        }
+    }
+}
+
+/// Generated content of an [`OctreeUpdate`], see [`ChunkOctree::apply_update`]
+pub struct GeneratedUpdate {
+    update: OctreeUpdate,
+    content: NodeContent,
+}
+
+/// World made of chunks at varying scales, nodes near loaders are split into finer chunks
+///
+/// Memory is bounded by the number of leaves, which depends on "split_distance" and the number
+/// of levels, not on the size of the world. Chunks of the octree can't be edited, use
+/// [`ChunksHolder`] for editable worlds.
+///
+/// [`ChunksHolder`]: super::ChunksHolder
+pub struct ChunkOctree {
+    /// 8 nodes at "max_level" around the origin
+    roots: Vec<OctreeNode>,
+    generator: Arc<dyn DensityGenerator>,
+    mesh_settings: MeshSettings,
+}
+
+impl ChunkOctree {
+    pub fn new(max_level: u8, generator: Arc<dyn DensityGenerator>, pool: &TaskPool) -> Self {
+        let roots = pool.scope(|scope| {
+            for index in 0..8 {
+                let generator = &generator;
+                scope.spawn(async move {
+                    // roots are children of a virtual node centered at the origin
+                    let pos = OctreeNode::get_child_pos(Position::new(0, 0, 0), index)
+                        - Position::new(1, 1, 1);
+                    OctreeNode::new_leaf(max_level, pos, generator)
+                });
+            }
+        });
+
+        Self {
+            roots,
+            generator,
+            mesh_settings: MeshSettings::default(),
+        }
+    }
+
+    pub fn get_mesh_settings(&self) -> &MeshSettings {
+        &self.mesh_settings
+    }
+
+    /// Change meshing options and redraw all leaves, iso level is clamped to [`DENSITY_LIMIT`]
+    ///
+    /// [`DENSITY_LIMIT`]: super::voxel::DENSITY_LIMIT
+    pub fn set_mesh_settings(&mut self, settings: MeshSettings) {
+        self.mesh_settings = settings.clamped();
+        for leaf in self.query_leaves_mut(|_| true) {
+            if let Some(chunk) = leaf.get_chunk_mut() {
+                chunk.set_need_update();
+            }
+        }
+    }
+
+    /// Deepest node covering node at "level" and "pos", but not deeper than "level"
+    pub fn get_node(&self, level: u8, pos: Position) -> Option<&OctreeNode> {
+        let mut node = self.roots.iter().find(|root| root.contains(level, pos))?;
+        while node.level > level {
+            match &node.content {
+                NodeContent::Branch(children) => node = &children[node.get_child_index(level, pos)],
+                NodeContent::Leaf(_) => break,
+            }
+        }
+
+        Some(node)
+    }
+
+    pub fn get_node_mut(&mut self, level: u8, pos: Position) -> Option<&mut OctreeNode> {
+        self.roots
+            .iter_mut()
+            .find(|root| root.contains(level, pos))
+            .map(|root| root.get_node_mut(level, pos))
+    }
+
+    /// Leaf containing world point "point"
+    pub fn get_leaf(&self, point: Vec3) -> Option<&OctreeNode> {
+        let pos = Position::from_vec(point).div_floor(CHUNK_REAL_SIZE as i64);
+        self.get_node(0, pos).filter(|node| node.is_leaf())
+    }
+
+    /// Leaves intersecting box from "min" to "max" in world coordinates
+    pub fn query_aabb(&self, min: Vec3, max: Vec3) -> Vec<&OctreeNode> {
+        self.query_leaves(|aabb| Self::intersects(aabb, min, max))
+    }
+
+    /// Leaves at least partially visible from camera with "frustum"
+    pub fn query_frustum(&self, frustum: &Frustum) -> Vec<&OctreeNode> {
+        self.query_leaves(|aabb| frustum.intersects_obb(aabb, &Mat4::IDENTITY, true))
+    }
+
+    pub fn leaves(&self) -> Vec<&OctreeNode> {
+        self.query_leaves(|_| true)
+    }
+
+    fn query_leaves(&self, filter: impl Fn(&Aabb) -> bool) -> Vec<&OctreeNode> {
+        let mut leaves = Vec::new();
+        for root in self.roots.iter() {
+            root.collect_leaves(&filter, &mut leaves);
+        }
+        leaves
+    }
+
+    fn query_leaves_mut(&mut self, filter: impl Fn(&Aabb) -> bool) -> Vec<&mut OctreeNode> {
+        let mut leaves = Vec::new();
+        for root in self.roots.iter_mut() {
+            root.collect_leaves_mut(&filter, &mut leaves);
+        }
+        leaves
+    }
+
+    fn intersects(aabb: &Aabb, min: Vec3, max: Vec3) -> bool {
+        Vec3::from(aabb.min()).cmple(max).all() && Vec3::from(aabb.max()).cmpge(min).all()
+    }
+
+    /// Splits of nodes near "loaders" and merges of nodes far from all of them
+    ///
+    /// Chunks of changed nodes are generated with [`ChunkOctree::get_update_job`] and applied
+    /// with [`ChunkOctree::apply_update`].
+    pub fn get_updates(&self, loaders: &[Vec3], settings: &OctreeSettings) -> Vec<OctreeUpdate> {
+        let mut updates = Vec::new();
+        for root in self.roots.iter() {
+            Self::collect_updates(root, loaders, settings, &mut updates);
+        }
+        updates
+    }
+
+    pub fn get_update_job(&self, update: OctreeUpdate) -> OctreeUpdateJob {
+        OctreeUpdateJob {
+            update,
+            generator: self.generator.clone(),
+        }
+    }
+
+    /// Replace node changed by "generated" update, returns removed leaves so their entities can
+    /// be despawned
+    ///
+    /// Updates of nodes which were changed since the update was requested are dropped.
+    pub fn apply_update(&mut self, generated: GeneratedUpdate) -> Vec<OctreeNode> {
+        let (level, pos) = generated.update.get_node();
+        let node = match self.get_node_mut(level, pos) {
+            Some(node) if node.level == level => node,
+            _ => return Vec::new(),
+        };
+        match (&generated.update, &node.content) {
+            (OctreeUpdate::Split { .. }, NodeContent::Leaf(_))
+            | (OctreeUpdate::Merge { .. }, NodeContent::Branch(_)) => {}
+            _ => return Vec::new(),
+        }
+
+        let old = OctreeNode {
+            level,
+            pos,
+            content: std::mem::replace(&mut node.content, generated.content),
+        };
+        let mut removed = Vec::new();
+        old.into_leaves(&mut removed);
+
+        // neighbours need to update skirts at the shared faces
+        let aabb = node.get_aabb();
+        let (min, max) = (Vec3::from(aabb.min()), Vec3::from(aabb.max()));
+        for leaf in self.query_leaves_mut(|aabb| Self::intersects(aabb, min, max)) {
+            if let Some(chunk) = leaf.get_chunk_mut() {
+                chunk.set_need_update();
+            }
+        }
+
+        removed
+    }
+
+    fn collect_updates(
+        node: &OctreeNode,
+        loaders: &[Vec3],
+        settings: &OctreeSettings,
+        updates: &mut Vec<OctreeUpdate>,
+    ) {
+        // distance to the nearest loader in node sizes
+        let aabb = node.get_aabb();
+        let (min, max) = (Vec3::from(aabb.min()), Vec3::from(aabb.max()));
+        let distance = loaders
+            .iter()
+            .map(|loader| loader.distance(loader.clamp(min, max)))
+            .fold(f32::INFINITY, f32::min)
+            / (aabb.half_extents.x * 2.);
+
+        match &node.content {
+            NodeContent::Leaf(_) if node.level > 0 && distance < settings.split_distance => updates
+                .push(OctreeUpdate::Split {
+                    level: node.level,
+                    pos: node.pos,
+                }),
+            NodeContent::Branch(_) if distance >= settings.merge_distance => {
+                updates.push(OctreeUpdate::Merge {
+                    level: node.level,
+                    pos: node.pos,
+                })
+            }
+            NodeContent::Branch(children) => children
+                .iter()
+                .for_each(|child| Self::collect_updates(child, loaders, settings, updates)),
+            NodeContent::Leaf(_) => {}
+        }
+    }
+
+    /// Data to mesh leaf at "level" and "pos" on another thread, None if there is no such leaf
+    pub fn get_mesh_job(&self, level: u8, pos: Position) -> Option<LeafMeshJob> {
+        let leaf = self
+            .get_node(level, pos)
+            .filter(|node| node.level == level && node.is_leaf())?;
+
+        Some(LeafMeshJob {
+            chunk: leaf.get_chunk()?.clone(),
+            faces: self.get_skirt_faces(leaf),
+            generator: ScaledGenerator::new(self.generator.clone(), 1 << level),
+            settings: self.mesh_settings,
+        })
+    }
+
+    /// Faces of "leaf" shared with nodes of other levels
+    fn get_skirt_faces(&self, leaf: &OctreeNode) -> Vec<SkirtFace> {
+        let mut faces = Vec::new();
+        for axis in 0..3 {
+            for side in [-1, 1] {
+                let mut offset = [0; 3];
+                offset[axis] = side;
+
+                let pos = leaf.pos + Position::new(offset[0], offset[1], offset[2]);
+                let depth = match self.get_node(leaf.level, pos) {
+                    // coarser neighbour, its surface can be off by one of its voxels
+                    Some(node) if node.level > leaf.level => {
+                        (1 << (node.level - leaf.level)) as f32
+                    }
+                    Some(node) if !node.is_leaf() => 1.,
+                    _ => continue,
+                };
+
+                faces.push(SkirtFace {
+                    axis,
+                    coordinate: if side < 0 { 0. } else { CHUNK_REAL_SIZE as f32 },
+                    depth,
+                });
+            }
+        }
+
+        faces
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::chunks::resources::generator::DefaultGenerator;
+
+    fn new_octree(max_level: u8) -> ChunkOctree {
+        ChunkOctree::new(
+            max_level,
+            Arc::new(DefaultGenerator::default()),
+            &TaskPool::new(),
+        )
+    }
+
+    fn settings(max_level: u8) -> OctreeSettings {
+        OctreeSettings {
+            max_level,
+            ..Default::default()
+        }
+    }
+
+    /// Apply updates until the octree is stable, returns number of applied updates
+    fn update(octree: &mut ChunkOctree, loaders: &[Vec3], settings: &OctreeSettings) -> usize {
+        let mut count = 0;
+        for _ in 0..32 {
+            let updates = octree.get_updates(loaders, settings);
+            if updates.is_empty() {
+                return count;
+            }
+            for update in updates {
+                let generated = octree.get_update_job(update).generate();
+                octree.apply_update(generated);
+                count += 1;
+            }
+        }
+        panic!("octree is not stable");
+    }
+
+    /// Leaves must cover the whole world without overlaps
+    fn assert_covered(octree: &ChunkOctree, max_level: u8) {
+        let volume: i64 = octree
+            .leaves()
+            .iter()
+            .map(|leaf| leaf.get_scale().pow(3))
+            .sum();
+        assert_eq!(volume, 8 * (1i64 << max_level).pow(3));
+    }
+
+    #[test]
+    fn splits_near_loader_and_merges_back() {
+        let mut octree = new_octree(2);
+        let settings = settings(2);
+        assert_eq!(octree.leaves().len(), 8);
+
+        let loader = Vec3::new(10., 5., 10.);
+        assert!(update(&mut octree, &[loader], &settings) > 0);
+        assert_covered(&octree, 2);
+        assert!(octree.leaves().len() > 8);
+        assert_eq!(
+            octree.get_leaf(loader).map(|leaf| leaf.get_level()),
+            Some(0)
+        );
+        // split leaves need redraw
+        assert!(octree
+            .leaves()
+            .iter()
+            .all(|leaf| leaf.get_chunk().unwrap().is_need_update()));
+
+        update(&mut octree, &[Vec3::splat(1e5)], &settings);
+        assert_covered(&octree, 2);
+        assert_eq!(octree.leaves().len(), 8);
+        assert!(octree.leaves().iter().all(|leaf| leaf.get_level() == 2));
+    }
+
+    #[test]
+    fn split_and_merge_thresholds_differ() {
+        let mut octree = new_octree(1);
+        let settings = settings(1);
+        // root at the origin is 64 voxels wide, loaders are in front of its +x face
+        let root = (1, Position::new(0, 0, 0));
+        let loader = |distance: f32| [Vec3::new(64. + distance, 32., 32.)];
+        let updates_of_root = |octree: &ChunkOctree, distance: f32| {
+            octree
+                .get_updates(&loader(distance), &settings)
+                .into_iter()
+                .filter(|update| update.get_node() == root)
+                .collect::<Vec<_>>()
+        };
+
+        assert!(updates_of_root(&octree, 100.).is_empty());
+        assert_eq!(
+            updates_of_root(&octree, 90.),
+            vec![OctreeUpdate::Split {
+                level: 1,
+                pos: root.1
+            }]
+        );
+        update(&mut octree, &loader(90.), &settings);
+
+        // loader moved back past the split distance, but not past the merge distance
+        assert!(updates_of_root(&octree, 100.).is_empty());
+        assert_eq!(
+            updates_of_root(&octree, 130.),
+            vec![OctreeUpdate::Merge {
+                level: 1,
+                pos: root.1
+            }]
+        );
+    }
+
+    #[test]
+    fn stale_updates_are_dropped() {
+        let mut octree = new_octree(1);
+        let split = OctreeUpdate::Split {
+            level: 1,
+            pos: Position::new(0, 0, 0),
+        };
+
+        let first = octree.get_update_job(split).generate();
+        let second = octree.get_update_job(split).generate();
+        assert_eq!(octree.apply_update(first).len(), 1);
+        // node is not a leaf anymore
+        assert!(octree.apply_update(second).is_empty());
+        assert_eq!(octree.leaves().len(), 15);
+    }
+
+    #[test]
+    fn point_and_box_queries() {
+        let mut octree = new_octree(2);
+        update(&mut octree, &[Vec3::new(20., 20., 20.)], &settings(2));
+
+        for point in [Vec3::new(20., 20., 20.), Vec3::new(-100., 3., 90.)] {
+            let leaf = octree
+                .get_leaf(point)
+                .expect("point is inside of the world");
+            assert!(leaf.is_leaf());
+            let aabb = leaf.get_aabb();
+            assert!(Vec3::from(aabb.min()).cmple(point).all());
+            assert!(Vec3::from(aabb.max()).cmpgt(point).all());
+        }
+        assert!(octree.get_leaf(Vec3::splat(1e4)).is_none());
+
+        let (min, max) = (Vec3::new(-10., 0., 5.), Vec3::new(40., 20., 70.));
+        let found: Vec<(u8, Position)> = octree
+            .query_aabb(min, max)
+            .iter()
+            .map(|leaf| (leaf.get_level(), leaf.get_pos()))
+            .collect();
+        let expected: Vec<(u8, Position)> = octree
+            .leaves()
+            .into_iter()
+            .filter(|leaf| ChunkOctree::intersects(&leaf.get_aabb(), min, max))
+            .map(|leaf| (leaf.get_level(), leaf.get_pos()))
+            .collect();
+        assert!(!found.is_empty() && found.len() < octree.leaves().len());
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn frustum_query() {
+        let octree = new_octree(1);
+
+        // orthographic camera looking along -z at a 20x20 square in x, y from 30 to 50
+        let translation = Vec3::new(40., 40., 200.);
+        let view = Mat4::from_translation(translation);
+        let projection = Mat4::orthographic_rh(-10., 10., -10., 10., 0.1, 1000.);
+        let get_frustum = |far: f32| {
+            Frustum::from_view_projection(
+                &(projection * view.inverse()),
+                &translation,
+                &Vec3::Z,
+                far,
+            )
+        };
+
+        let visible: Vec<Position> = octree
+            .query_frustum(&get_frustum(1000.))
+            .iter()
+            .map(|leaf| leaf.get_pos())
+            .collect();
+        assert_eq!(visible.len(), 2);
+        assert!(visible
+            .iter()
+            .all(|pos| pos.x == 0 && pos.y == 0 && (pos.z == 0 || pos.z == -1)));
+
+        // far plane in front of the world
+        assert!(octree.query_frustum(&get_frustum(100.)).is_empty());
+    }
+}
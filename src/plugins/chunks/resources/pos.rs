@@ -2,7 +2,7 @@ use std::ops::{Add, Mul, Sub};
 
 use bevy::math::Vec3;
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub struct Position {
     pub x: i64,
     pub y: i64,
@@ -14,9 +14,31 @@ impl Position {
         Self { x, y, z }
     }
 
+    /// Position of the voxel containing world point "vec"
+    pub fn from_vec(vec: Vec3) -> Self {
+        Self::new(
+            vec.x.floor() as i64,
+            vec.y.floor() as i64,
+            vec.z.floor() as i64,
+        )
+    }
+
     pub fn to_vec(&self) -> Vec3 {
         Vec3::new(self.x as f32, self.y as f32, self.z as f32)
     }
+
+    /// Divide each component rounding towards negative infinity
+    pub fn div_floor(&self, rhs: i64) -> Self {
+        Self::new(
+            self.x.div_euclid(rhs),
+            self.y.div_euclid(rhs),
+            self.z.div_euclid(rhs),
+        )
+    }
+
+    pub fn length_squared(&self) -> i64 {
+        self.x * self.x + self.y * self.y + self.z * self.z
+    }
 }
 
 impl Add for Position {
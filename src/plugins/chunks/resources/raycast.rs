@@ -0,0 +1,199 @@
+use super::{mesh::normals::get_gradient, pos::Position, ChunksHolder};
+use bevy::math::Vec3;
+
+/// Number of density samples along the ray inside each cell which can contain the surface
+const CELL_SAMPLES: usize = 4;
+const BISECTION_STEPS: usize = 16;
+
+/// Result of [`ChunksHolder::raycast`]
+#[derive(Debug, Clone, Copy)]
+pub struct RaycastHit {
+    /// point where the ray crosses the surface
+    pub position: Vec3,
+    /// surface normal, points out of the filled volume
+    pub normal: Vec3,
+    /// distance from the ray origin to "position"
+    pub distance: f32,
+    /// position of chunk containing "voxel"
+    pub chunk: Position,
+    /// world position of the filled voxel nearest to the hit
+    pub voxel: Position,
+}
+
+/// Step through cells along the ray (DDA) and find the surface crossing in the first cell
+/// with filled corners by sampling and bisection of trilinearly interpolated density
+pub(super) fn raycast(
+    chunks: &ChunksHolder,
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+) -> Option<RaycastHit> {
+    let iso_level = chunks.get_mesh_settings().iso_level;
+    let direction = direction.normalize_or_zero();
+    if direction == Vec3::ZERO {
+        return None;
+    }
+
+    if get_density(chunks, origin)? >= iso_level {
+        return get_hit(chunks, origin, direction, 0., iso_level);
+    }
+
+    let mut cell = Position::from_vec(origin);
+    let step = direction.signum();
+    // ray length to cross a cell along each axis
+    let t_delta = direction.abs().recip();
+    // ray length to the next cell border along each axis, infinite if the ray is parallel to it
+    let mut t_next = Vec3::splat(f32::INFINITY);
+    for axis in 0..3 {
+        if direction[axis] > 0. {
+            t_next[axis] = (cell.to_vec()[axis] + 1. - origin[axis]) * t_delta[axis];
+        } else if direction[axis] < 0. {
+            t_next[axis] = (origin[axis] - cell.to_vec()[axis]) * t_delta[axis];
+        }
+    }
+
+    // density at "t" is always below iso level
+    let mut t = 0.;
+    while t < max_distance {
+        let t_exit = t_next.min_element().min(max_distance);
+
+        // trilinear density can only reach iso level if one of the corners does
+        if has_filled_corner(chunks, cell, iso_level)? {
+            let mut t_empty = t;
+            for sample in 1..=CELL_SAMPLES {
+                let t_sample = t + (t_exit - t) * sample as f32 / CELL_SAMPLES as f32;
+                if get_density(chunks, origin + direction * t_sample)? < iso_level {
+                    t_empty = t_sample;
+                    continue;
+                }
+
+                // density is below iso level at "t_empty" and above at "t_filled"
+                let mut t_filled = t_sample;
+                for _ in 0..BISECTION_STEPS {
+                    let t_middle = (t_empty + t_filled) / 2.;
+                    if get_density(chunks, origin + direction * t_middle)? < iso_level {
+                        t_empty = t_middle;
+                    } else {
+                        t_filled = t_middle;
+                    }
+                }
+
+                return get_hit(chunks, origin, direction, t_filled, iso_level);
+            }
+        }
+
+        // step to the neighbour cell through the nearest border
+        let axis = if t_next.x <= t_next.y && t_next.x <= t_next.z {
+            0
+        } else if t_next.y <= t_next.z {
+            1
+        } else {
+            2
+        };
+        match axis {
+            0 => cell.x += step.x as i64,
+            1 => cell.y += step.y as i64,
+            _ => cell.z += step.z as i64,
+        }
+        t_next[axis] += t_delta[axis];
+        t = t_exit;
+    }
+
+    None
+}
+
+fn get_hit(
+    chunks: &ChunksHolder,
+    origin: Vec3,
+    direction: Vec3,
+    distance: f32,
+    iso_level: f32,
+) -> Option<RaycastHit> {
+    let position = origin + direction * distance;
+
+    let sample = |pos: Position| {
+        chunks
+            .get_voxel_world(pos)
+            .map(|voxel| voxel.value)
+            .unwrap_or(iso_level)
+    };
+    // density grows into the filled volume
+    let normal = -get_gradient(&sample, position).normalize_or_zero();
+
+    let base = Position::from_vec(position);
+    let voxel = get_corners(base)
+        .filter(|&corner| {
+            chunks
+                .get_voxel_world(corner)
+                .is_some_and(|voxel| voxel.is_filled(iso_level))
+        })
+        .min_by(|a, b| {
+            let a = a.to_vec().distance_squared(position);
+            let b = b.to_vec().distance_squared(position);
+            a.total_cmp(&b)
+        })?;
+
+    Some(RaycastHit {
+        position,
+        normal,
+        distance,
+        chunk: ChunksHolder::get_chunk_pos(voxel),
+        voxel,
+    })
+}
+
+fn get_corners(cell: Position) -> impl Iterator<Item = Position> {
+    (0..8).map(move |corner| cell + Position::new(corner & 1, (corner >> 1) & 1, corner >> 2))
+}
+
+/// None if some of the corners are not loaded
+fn has_filled_corner(chunks: &ChunksHolder, cell: Position, iso_level: f32) -> Option<bool> {
+    let mut is_filled = false;
+    for corner in get_corners(cell) {
+        is_filled |= chunks.get_voxel_world(corner)?.is_filled(iso_level);
+    }
+
+    Some(is_filled)
+}
+
+/// Density at point "p" trilinearly interpolated from voxels of its cell
+fn get_density(chunks: &ChunksHolder, p: Vec3) -> Option<f32> {
+    let base = Position::from_vec(p);
+    let t = p - base.to_vec();
+
+    let mut value = 0.;
+    for corner in get_corners(base) {
+        let offset = corner - base;
+        let weight = if offset.x == 0 { 1. - t.x } else { t.x }
+            * if offset.y == 0 { 1. - t.y } else { t.y }
+            * if offset.z == 0 { 1. - t.z } else { t.z };
+        value += chunks.get_voxel_world(corner)?.value * weight;
+    }
+
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::chunks::resources::generator::DefaultGenerator;
+    use bevy::tasks::TaskPool;
+    use std::sync::Arc;
+
+    #[test]
+    fn hits_default_terrain_from_above() {
+        let chunks = ChunksHolder::new(2, Arc::new(DefaultGenerator::default()), &TaskPool::new());
+        let origin = Vec3::new(3., 20., 5.);
+
+        let hit = chunks
+            .raycast(origin, Vec3::new(0., -1., 0.), 100.)
+            .expect("ray hits the ground");
+        // surface of the default terrain is between -5 and 5
+        assert!(hit.distance > 10. && hit.distance < 30., "{:?}", hit);
+        assert!((hit.position.y + hit.distance - origin.y).abs() < 1e-3);
+        assert!(hit.normal.y > 0.5, "{:?}", hit);
+
+        // rays into the sky hit nothing
+        assert!(chunks.raycast(origin, Vec3::Y, 100.).is_none());
+    }
+}
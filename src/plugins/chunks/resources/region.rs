@@ -0,0 +1,619 @@
+use super::{
+    chunk::{Chunk, CHUNK_VOLUME},
+    generator::DensityGenerator,
+    pos::Position,
+    voxel::Voxel,
+};
+use bevy::{
+    log::error,
+    utils::{HashMap, HashSet},
+};
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Number of chunks along each axis of a region
+pub const REGION_SIZE: i64 = 16;
+const REGION_VOLUME: usize = (REGION_SIZE * REGION_SIZE * REGION_SIZE) as usize;
+
+const REGION_MAGIC: &[u8; 4] = b"MCRG";
+/// Version of region file format, bump it and add a migration of older records on format changes
+pub const REGION_VERSION: u16 = 1;
+/// Magic, version, reserved u16 and offset table
+const HEADER_SIZE: usize = 4 + 2 + 2 + REGION_VOLUME * 8;
+
+/// Encoded chunk records of a region, indexed by in-region chunk index
+type RegionRecords = Vec<Option<Vec<u8>>>;
+
+/// Compression of a single chunk record
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum Compression {
+    None = 0,
+    /// run length encoding of voxel bytes split into planes
+    Rle = 1,
+}
+
+/// Region files with voxels of modified chunks
+///
+/// Each file stores up to [`REGION_SIZE`]³ chunks. File starts with a header (magic, format
+/// version and table with offset and length of each chunk record), followed by compressed
+/// chunk records. Regions are cached in memory once read, cloning shares the cache.
+///
+/// Chunks can be queued with [`RegionStorage::queue`] and written later with
+/// [`RegionStorage::flush`], queued chunks are loaded from memory until they are written.
+#[derive(Clone)]
+pub struct RegionStorage {
+    dir: Arc<PathBuf>,
+    /// records of regions which were already read
+    regions: Arc<Mutex<HashMap<Position, RegionRecords>>>,
+    unsaved: Arc<Mutex<UnsavedChunks>>,
+    /// held while regions are written, so they are written in the order they were changed
+    writing: Arc<Mutex<()>>,
+}
+
+/// Modified chunks waiting for [`RegionStorage::flush`]
+#[derive(Default)]
+struct UnsavedChunks {
+    next_id: u64,
+    /// chunk with id of the queue call, so chunks queued again during flush are not dropped
+    chunks: HashMap<Position, (u64, Chunk)>,
+}
+
+impl RegionStorage {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: Arc::new(dir.into()),
+            regions: Default::default(),
+            unsaved: Default::default(),
+            writing: Default::default(),
+        }
+    }
+
+    /// Load saved chunk at "pos", returns None if it was never saved
+    pub fn load(&self, pos: Position) -> io::Result<Option<Chunk>> {
+        if let Some((_, chunk)) = self.unsaved.lock().unwrap().chunks.get(&pos) {
+            // queued copy is written by the next flush
+            let mut chunk = chunk.clone();
+            chunk.set_saved();
+            return Ok(Some(chunk));
+        }
+
+        let (region_pos, index) = Self::get_region_pos(pos);
+        let cached = self
+            .regions
+            .lock()
+            .unwrap()
+            .get(&region_pos)
+            .map(|records| records[index].clone());
+        let record = match cached {
+            Some(record) => record,
+            None => {
+                let records = self.read_region(region_pos)?;
+                // region could be cached by another thread in the meantime
+                let mut regions = self.regions.lock().unwrap();
+                regions.entry(region_pos).or_insert(records)[index].clone()
+            }
+        };
+
+        match record {
+            Some(record) => Ok(Some(Chunk::from_saved(pos, decode_record(&record)?))),
+            None => Ok(None),
+        }
+    }
+
+    /// Load saved chunk at "pos" or generate it if there is none
+    ///
+    /// Read errors are logged and the chunk is generated instead.
+    pub fn load_or_generate(&self, pos: Position, generator: &dyn DensityGenerator) -> Chunk {
+        match self.load(pos) {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => Chunk::new(pos, generator),
+            Err(err) => {
+                error!("failed to load chunk {:?}: {}", pos, err);
+                Chunk::new(pos, generator)
+            }
+        }
+    }
+
+    /// Write chunks with unsaved changes to their region files, other chunks are skipped
+    pub fn save<'a>(&self, chunks: impl IntoIterator<Item = &'a Chunk>) -> io::Result<()> {
+        self.queue(chunks.into_iter().cloned());
+        self.flush()
+    }
+
+    /// Keep chunks with unsaved changes in memory until the next [`RegionStorage::flush`]
+    pub fn queue(&self, chunks: impl IntoIterator<Item = Chunk>) {
+        let mut unsaved = self.unsaved.lock().unwrap();
+        for chunk in chunks.into_iter().filter(|chunk| chunk.is_unsaved()) {
+            let id = unsaved.next_id;
+            unsaved.next_id += 1;
+            unsaved.chunks.insert(chunk.get_pos(), (id, chunk));
+        }
+    }
+
+    pub fn has_unsaved(&self) -> bool {
+        !self.unsaved.lock().unwrap().chunks.is_empty()
+    }
+
+    /// Write queued chunks to their region files
+    ///
+    /// Chunks are encoded and regions are read and written without holding the cache lock, so
+    /// loading isn't blocked. Regions which can't be read are moved aside and started anew.
+    /// Other regions are written even if one fails, chunks which failed to save stay queued and
+    /// the last error is returned.
+    pub fn flush(&self) -> io::Result<()> {
+        let _writing = self.writing.lock().unwrap();
+
+        let unsaved: Vec<(u64, Chunk)> = self
+            .unsaved
+            .lock()
+            .unwrap()
+            .chunks
+            .values()
+            .cloned()
+            .collect();
+        if unsaved.is_empty() {
+            return Ok(());
+        }
+
+        let mut changed: HashMap<Position, Vec<(usize, Vec<u8>)>> = HashMap::default();
+        for (_, chunk) in unsaved.iter() {
+            let (region_pos, index) = Self::get_region_pos(chunk.get_pos());
+            changed
+                .entry(region_pos)
+                .or_default()
+                .push((index, encode_record(&chunk.get_voxels())));
+        }
+
+        fs::create_dir_all(self.dir.as_ref())?;
+        let mut result = Ok(());
+        let mut failed: HashSet<Position> = HashSet::default();
+        for (region_pos, records) in changed {
+            // loads of other chunks of the region read the file while it's out of the cache,
+            // changed chunks are still loaded from the queue
+            let cached = self.regions.lock().unwrap().remove(&region_pos);
+            let mut region = match cached {
+                Some(region) => region,
+                None => match self.read_region(region_pos) {
+                    Ok(region) => region,
+                    Err(err) => {
+                        error!("failed to read region {:?}: {}", region_pos, err);
+                        // bad file is kept, so chunks saved in it can be recovered
+                        match self.move_region_aside(region_pos) {
+                            Ok(()) => vec![None; REGION_VOLUME],
+                            Err(err) => {
+                                failed.insert(region_pos);
+                                result = Err(err);
+                                continue;
+                            }
+                        }
+                    }
+                },
+            };
+            for (index, record) in records {
+                region[index] = Some(record);
+            }
+
+            let written = self.write_region(region_pos, &region);
+            self.regions.lock().unwrap().insert(region_pos, region);
+            if let Err(err) = written {
+                failed.insert(region_pos);
+                result = Err(err);
+            }
+        }
+
+        // chunks are in the cache now, unless they were queued again
+        let mut unsaved_chunks = self.unsaved.lock().unwrap();
+        for (id, chunk) in unsaved {
+            let pos = chunk.get_pos();
+            if failed.contains(&Self::get_region_pos(pos).0) {
+                continue;
+            }
+            if unsaved_chunks.chunks.get(&pos).map(|(queued, _)| *queued) == Some(id) {
+                unsaved_chunks.chunks.remove(&pos);
+            }
+        }
+
+        result
+    }
+
+    /// Region position and index of chunk at "pos" in the region
+    fn get_region_pos(pos: Position) -> (Position, usize) {
+        let region_pos = pos.div_floor(REGION_SIZE);
+        let local = pos - region_pos * REGION_SIZE;
+        let index = local.x + local.y * REGION_SIZE + local.z * REGION_SIZE * REGION_SIZE;
+
+        (region_pos, index as usize)
+    }
+
+    fn get_region_path(&self, region_pos: Position) -> PathBuf {
+        self.dir.join(format!(
+            "r.{}.{}.{}.region",
+            region_pos.x, region_pos.y, region_pos.z
+        ))
+    }
+
+    /// Records of region at "region_pos" from disk, all records are empty if there is no file
+    fn read_region(&self, region_pos: Position) -> io::Result<RegionRecords> {
+        match fs::read(self.get_region_path(region_pos)) {
+            Ok(data) => parse_region(&data),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(vec![None; REGION_VOLUME]),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Rename region file which can't be read, so a new one can be written in its place
+    fn move_region_aside(&self, region_pos: Position) -> io::Result<()> {
+        let path = self.get_region_path(region_pos);
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_millis())
+            .unwrap_or_default();
+        let bad_path = path.with_extension(format!("region.{}.bad", time));
+
+        error!("moving region file {:?} to {:?}", path, bad_path);
+        fs::rename(path, bad_path)
+    }
+
+    /// Write region to a temporary file and replace the old one, so it's never left half-written
+    fn write_region(&self, region_pos: Position, records: &[Option<Vec<u8>>]) -> io::Result<()> {
+        let mut data = Vec::with_capacity(HEADER_SIZE);
+        data.extend_from_slice(REGION_MAGIC);
+        data.extend_from_slice(&REGION_VERSION.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+
+        // zero offset marks missing chunk
+        let mut offset = HEADER_SIZE;
+        for record in records {
+            let (record_offset, length) = match record {
+                Some(record) => (offset, record.len()),
+                None => (0, 0),
+            };
+            data.extend_from_slice(&(record_offset as u32).to_le_bytes());
+            data.extend_from_slice(&(length as u32).to_le_bytes());
+            offset += length;
+        }
+        for record in records.iter().flatten() {
+            data.extend_from_slice(record);
+        }
+
+        let path = self.get_region_path(region_pos);
+        let tmp_path = path.with_extension("region.tmp");
+        fs::write(&tmp_path, data)?;
+        fs::rename(tmp_path, path)
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Split region file into chunk records of the current version
+fn parse_region(data: &[u8]) -> io::Result<RegionRecords> {
+    if data.len() < HEADER_SIZE || &data[0..4] != REGION_MAGIC {
+        return Err(invalid_data("not a region file".to_string()));
+    }
+    let version = u16::from_le_bytes([data[4], data[5]]);
+
+    (0..REGION_VOLUME)
+        .map(|index| {
+            let entry = 8 + index * 8;
+            let offset = read_u32(data, entry) as usize;
+            let length = read_u32(data, entry + 4) as usize;
+            if offset == 0 {
+                return Ok(None);
+            }
+
+            let record = data
+                .get(offset..offset + length)
+                .ok_or_else(|| invalid_data(format!("chunk record {} is out of file", index)))?;
+            migrate_record(version, record.to_vec()).map(Some)
+        })
+        .collect()
+}
+
+/// Convert chunk record written with format "version" to the current format
+fn migrate_record(version: u16, record: Vec<u8>) -> io::Result<Vec<u8>> {
+    match version {
+        REGION_VERSION => Ok(record),
+        // conversions from older versions go here
+        _ => Err(invalid_data(format!(
+            "unsupported region version {}",
+            version
+        ))),
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+/// Bytes of voxels grouped in planes: byte of density by byte, then materials
+///
+/// Neighbour voxels have similar densities, so planes have long runs of equal bytes.
+fn encode_record(voxels: &[Voxel]) -> Vec<u8> {
+    let mut planes = Vec::with_capacity(voxels.len() * 5);
+    for byte in 0..4 {
+        planes.extend(voxels.iter().map(|voxel| voxel.value.to_le_bytes()[byte]));
+    }
+    planes.extend(voxels.iter().map(|voxel| voxel.material));
+
+    let mut record = vec![Compression::Rle as u8];
+    encode_rle(&planes, &mut record);
+    record
+}
+
+fn decode_record(record: &[u8]) -> io::Result<Vec<Voxel>> {
+    let planes = match record.split_first() {
+        Some((&compression, data)) if compression == Compression::None as u8 => data.to_vec(),
+        Some((&compression, data)) if compression == Compression::Rle as u8 => decode_rle(data)?,
+        _ => return Err(invalid_data("unknown chunk compression".to_string())),
+    };
+
+    if planes.len() != CHUNK_VOLUME * 5 {
+        return Err(invalid_data(format!(
+            "chunk record has {} bytes instead of {}",
+            planes.len(),
+            CHUNK_VOLUME * 5
+        )));
+    }
+
+    let voxels = (0..CHUNK_VOLUME)
+        .map(|index| {
+            let byte = |plane: usize| planes[plane * CHUNK_VOLUME + index];
+            Voxel {
+                value: f32::from_le_bytes([byte(0), byte(1), byte(2), byte(3)]),
+                material: byte(4),
+            }
+        })
+        .collect();
+
+    Ok(voxels)
+}
+
+/// PackBits encoding: header "n" below 128 is followed by n + 1 literal bytes,
+/// otherwise the next byte is repeated n - 125 times
+fn encode_rle(data: &[u8], out: &mut Vec<u8>) {
+    let mut literals: Vec<u8> = Vec::new();
+    let flush = |literals: &mut Vec<u8>, out: &mut Vec<u8>| {
+        for chunk in literals.chunks(128) {
+            out.push(chunk.len() as u8 - 1);
+            out.extend_from_slice(chunk);
+        }
+        literals.clear();
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let run = data[i..]
+            .iter()
+            .take(130)
+            .take_while(|&&byte| byte == data[i])
+            .count();
+
+        if run >= 3 {
+            flush(&mut literals, out);
+            out.push((run + 125) as u8);
+            out.push(data[i]);
+        } else {
+            literals.extend_from_slice(&data[i..i + run]);
+        }
+        i += run;
+    }
+    flush(&mut literals, out);
+}
+
+fn decode_rle(data: &[u8]) -> io::Result<Vec<u8>> {
+    let truncated = || invalid_data("truncated chunk record".to_string());
+
+    let mut out = Vec::with_capacity(CHUNK_VOLUME * 5);
+    let mut i = 0;
+    while i < data.len() {
+        let header = data[i] as usize;
+        if header < 128 {
+            let literals = data.get(i + 1..i + 2 + header).ok_or_else(truncated)?;
+            out.extend_from_slice(literals);
+            i += 2 + header;
+        } else {
+            let byte = *data.get(i + 1).ok_or_else(truncated)?;
+            out.resize(out.len() + header - 125, byte);
+            i += 2;
+        }
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// runs of equal voxels mixed with unique ones, so both rle headers are used
+    fn get_mixed_voxels() -> Vec<Voxel> {
+        (0..CHUNK_VOLUME)
+            .map(|index| Voxel {
+                value: if index % 7 == 0 {
+                    index as f32 * 0.37
+                } else {
+                    -1.
+                },
+                material: (index / 1000 % 3) as u8,
+            })
+            .collect()
+    }
+
+    /// Chunk with mixed voxels and unsaved changes
+    fn get_changed_chunk(pos: Position) -> Chunk {
+        let mut chunk = Chunk::from_saved(pos, get_mixed_voxels());
+        let origin = Position::new(0, 0, 0);
+        chunk.set_voxel(origin, chunk.get_voxel(origin));
+        chunk
+    }
+
+    fn assert_same(a: &[Voxel], b: &[Voxel]) {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b.iter()) {
+            assert_eq!(a.value.to_bits(), b.value.to_bits());
+            assert_eq!(a.material, b.material);
+        }
+    }
+
+    fn get_temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("regions-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn record_round_trip() {
+        let voxels = get_mixed_voxels();
+        let record = encode_record(&voxels);
+        assert!(record.len() < CHUNK_VOLUME * 5);
+        assert_same(&decode_record(&record).unwrap(), &voxels);
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let dir = get_temp_dir("round-trip");
+        let pos = Position::new(-1, 2, -17);
+        let chunk = get_changed_chunk(pos);
+
+        RegionStorage::new(&dir).save([&chunk]).unwrap();
+
+        // new storage has no cache, so chunks are read from the file
+        let storage = RegionStorage::new(&dir);
+        let loaded = storage.load(pos).unwrap().expect("chunk is saved");
+        assert!(loaded.is_modified());
+        assert!(!loaded.is_unsaved());
+        assert_same(&loaded.get_voxels(), &chunk.get_voxels());
+        assert!(storage
+            .load(pos + Position::new(1, 0, 0))
+            .unwrap()
+            .is_none());
+        assert!(storage.load(Position::new(100, 0, 0)).unwrap().is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn queued_chunks_are_loaded_before_flush() {
+        let dir = get_temp_dir("queue");
+        let pos = Position::new(3, 0, 0);
+        let storage = RegionStorage::new(&dir);
+
+        storage.queue([get_changed_chunk(pos)]);
+        assert!(storage.has_unsaved());
+        assert!(storage.load(pos).unwrap().is_some());
+        assert!(!dir.exists());
+
+        storage.flush().unwrap();
+        assert!(!storage.has_unsaved());
+        assert!(RegionStorage::new(&dir).load(pos).unwrap().is_some());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn only_changed_chunks_are_saved() {
+        let dir = get_temp_dir("changed");
+        let pos = Position::new(0, -5, 2);
+        let storage = RegionStorage::new(&dir);
+
+        // loaded chunk differs from the generated one, but is already saved
+        storage.save([&get_changed_chunk(pos)]).unwrap();
+        let mut loaded = storage.load(pos).unwrap().unwrap();
+        storage.queue([loaded.clone()]);
+        assert!(!storage.has_unsaved());
+
+        loaded.set_voxel(Position::new(1, 2, 3), Voxel::default());
+        assert!(loaded.is_unsaved());
+        storage.queue([loaded]);
+        assert!(storage.has_unsaved());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn corrupt_region_is_moved_aside() {
+        let dir = get_temp_dir("corrupt");
+        let (bad, good) = (Position::new(1, 1, 1), Position::new(REGION_SIZE, 0, 0));
+        let storage = RegionStorage::new(&dir);
+
+        fs::create_dir_all(&dir).unwrap();
+        let bad_path = storage.get_region_path(RegionStorage::get_region_pos(bad).0);
+        fs::write(&bad_path, b"garbage").unwrap();
+
+        // other regions are written even though the bad one can't be read
+        storage
+            .save([&get_changed_chunk(bad), &get_changed_chunk(good)])
+            .unwrap();
+        assert!(!storage.has_unsaved());
+
+        let storage = RegionStorage::new(&dir);
+        assert!(storage.load(bad).unwrap().is_some());
+        assert!(storage.load(good).unwrap().is_some());
+
+        // bad file is kept next to the new region
+        let moved: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name.ends_with(".bad"))
+            .collect();
+        assert_eq!(moved.len(), 1);
+        assert_eq!(fs::read(dir.join(&moved[0])).unwrap(), b"garbage");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn corrupt_record_is_error() {
+        let record = encode_record(&get_mixed_voxels());
+
+        assert!(decode_record(&[]).is_err());
+        assert!(decode_record(&record[..record.len() / 2]).is_err());
+        assert!(decode_record(&record[..record.len() - 1]).is_err());
+        // unknown compression
+        assert!(decode_record(&[7, 0, 0]).is_err());
+        // literal header without its bytes
+        assert!(decode_rle(&[5, 1, 2]).is_err());
+        // run header without the repeated byte
+        assert!(decode_rle(&[200]).is_err());
+    }
+
+    #[test]
+    fn bad_header_is_error() {
+        let mut data = vec![0; HEADER_SIZE];
+        data[0..4].copy_from_slice(REGION_MAGIC);
+        data[4..6].copy_from_slice(&REGION_VERSION.to_le_bytes());
+        assert!(parse_region(&data).is_ok());
+
+        let mut bad_magic = data.clone();
+        bad_magic[0..4].copy_from_slice(b"NOPE");
+        assert!(parse_region(&bad_magic).is_err());
+        assert!(parse_region(&data[..HEADER_SIZE - 1]).is_err());
+
+        // unknown version with a chunk record
+        let mut bad_version = data.clone();
+        bad_version[4..6].copy_from_slice(&(REGION_VERSION + 1).to_le_bytes());
+        bad_version[8..12].copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+        bad_version.push(0);
+        bad_version[12..16].copy_from_slice(&1u32.to_le_bytes());
+        assert!(parse_region(&bad_version).is_err());
+
+        // record out of file
+        let mut out_of_file = data;
+        out_of_file[8..12].copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+        out_of_file[12..16].copy_from_slice(&64u32.to_le_bytes());
+        assert!(parse_region(&out_of_file).is_err());
+    }
+}
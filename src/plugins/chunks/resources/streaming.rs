@@ -0,0 +1,22 @@
+/// Limits of chunk streaming around [`ChunkLoader`] entities
+///
+/// [`ChunkLoader`]: crate::plugins::chunks::components::ChunkLoader
+pub struct StreamingSettings {
+    /// max chunks to generate per frame
+    pub load_budget: usize,
+    /// max chunks to unload per frame
+    pub unload_budget: usize,
+    /// extra distance (in chunks) after which loaded chunks are unloaded,
+    /// prevents reloading chunks when the loader moves back and forth across a chunk border
+    pub unload_margin: i64,
+}
+
+impl Default for StreamingSettings {
+    fn default() -> Self {
+        Self {
+            load_budget: 4,
+            unload_budget: 16,
+            unload_margin: 1,
+        }
+    }
+}
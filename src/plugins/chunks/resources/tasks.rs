@@ -0,0 +1,78 @@
+use super::{
+    chunk::Chunk,
+    mesh::{mesh_from_data, MeshData},
+    octree::{GeneratedUpdate, OctreeUpdate},
+    pos::Position,
+};
+use bevy::{
+    prelude::{Entity, Mesh},
+    render::primitives::Aabb,
+    tasks::Task,
+    utils::HashMap,
+};
+use std::io;
+
+/// Limits of background chunk jobs running on [`AsyncComputeTaskPool`]
+///
+/// [`AsyncComputeTaskPool`]: bevy::tasks::AsyncComputeTaskPool
+pub struct TasksSettings {
+    pub max_generation_tasks: usize,
+    pub max_meshing_tasks: usize,
+}
+
+impl Default for TasksSettings {
+    fn default() -> Self {
+        Self {
+            max_generation_tasks: 16,
+            max_meshing_tasks: 16,
+        }
+    }
+}
+
+/// Chunk jobs in flight, dropping a task cancels it
+#[derive(Default)]
+pub struct ChunkTasks {
+    pub generation: HashMap<Position, Task<Chunk>>,
+    pub meshing: HashMap<Position, Task<ChunkMeshes>>,
+    /// flush of unloaded chunks queued in [`RegionStorage`], one at a time
+    ///
+    /// [`RegionStorage`]: super::region::RegionStorage
+    pub saving: Option<Task<io::Result<()>>>,
+}
+
+impl ChunkTasks {
+    /// Cancel all jobs of chunk at "pos"
+    pub fn cancel(&mut self, pos: Position) {
+        self.generation.remove(&pos);
+        self.meshing.remove(&pos);
+    }
+}
+
+/// Jobs of [`ChunkOctree`], meshing jobs are keyed by level and position of the leaf
+///
+/// [`ChunkOctree`]: super::octree::ChunkOctree
+#[derive(Default)]
+pub struct OctreeTasks {
+    pub updates: HashMap<OctreeUpdate, Task<GeneratedUpdate>>,
+    pub meshing: HashMap<(u8, Position), Task<Mesh>>,
+    /// entities of removed leaves with their bounds, kept until leaves covering them are meshed
+    pub retired: Vec<(Entity, Aabb)>,
+}
+
+/// Result of chunk meshing job
+pub struct ChunkMeshes {
+    pub mesh: Mesh,
+    /// collider built from the same vertices as "mesh", None if there are no triangles
+    #[cfg(feature = "rapier")]
+    pub collider: Option<bevy_rapier3d::prelude::Collider>,
+}
+
+impl ChunkMeshes {
+    pub fn new(data: MeshData) -> Self {
+        Self {
+            #[cfg(feature = "rapier")]
+            collider: super::mesh::collider::collider_from_data(&data),
+            mesh: mesh_from_data(data),
+        }
+    }
+}
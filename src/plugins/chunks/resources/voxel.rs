@@ -1,4 +1,38 @@
-#[derive(Debug, Default, Clone, Copy)]
+/// Number of different materials voxels can have
+pub const MAX_MATERIALS: usize = 8;
+
+/// Materials of the default [`MaterialPalette`]
+///
+/// [`MaterialPalette`]: super::mesh::palette::MaterialPalette
+pub mod materials {
+    pub const DIRT: u8 = 0;
+    pub const ROCK: u8 = 1;
+    pub const SAND: u8 = 2;
+    pub const SNOW: u8 = 3;
+    pub const GRASS: u8 = 4;
+}
+
+/// Densities stored in chunks are clamped to [-DENSITY_LIMIT, DENSITY_LIMIT]
+pub const DENSITY_LIMIT: f32 = 16.;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub struct Voxel {
     pub value: f32,
+    /// index of material, less than [`MAX_MATERIALS`], only visible for filled voxels
+    pub material: u8,
+}
+
+impl Voxel {
+    /// Voxel is inside of the surface if its value is not less than "iso_level"
+    pub fn is_filled(&self, iso_level: f32) -> bool {
+        self.value >= iso_level
+    }
+
+    /// Voxel with density clamped to [`DENSITY_LIMIT`]
+    pub fn clamped(self) -> Self {
+        Self {
+            value: self.value.clamp(-DENSITY_LIMIT, DENSITY_LIMIT),
+            ..self
+        }
+    }
 }
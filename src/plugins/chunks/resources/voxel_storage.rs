@@ -0,0 +1,229 @@
+use super::voxel::Voxel;
+use std::mem::size_of;
+
+/// Compressed voxels of a chunk
+///
+/// Chunks far from the surface have all voxels clamped to the same density, so they store a
+/// single voxel. Chunks with long runs of equal voxels are run-length encoded, other chunks are
+/// stored as is.
+#[derive(Debug, Clone)]
+pub enum VoxelStorage {
+    /// all voxels are the same
+    Uniform {
+        voxel: Voxel,
+        len: usize,
+    },
+    /// runs of equal voxels, each run ends before its index
+    Rle(Vec<(u32, Voxel)>),
+    Dense(Vec<Voxel>),
+}
+
+impl VoxelStorage {
+    /// Pick the smallest representation of "voxels"
+    pub fn new(voxels: Vec<Voxel>) -> Self {
+        let mut runs: Vec<(u32, Voxel)> = Vec::new();
+        for (index, voxel) in voxels.iter().enumerate() {
+            match runs.last_mut() {
+                Some((end, last)) if is_same(last, voxel) => *end = index as u32 + 1,
+                _ => runs.push((index as u32 + 1, *voxel)),
+            }
+        }
+
+        match runs.len() {
+            0 | 1 => Self::Uniform {
+                voxel: runs.first().map(|(_, voxel)| *voxel).unwrap_or_default(),
+                len: voxels.len(),
+            },
+            // run is bigger than a voxel, rle only pays off when runs are long enough
+            len if len * size_of::<(u32, Voxel)>() * 2 <= voxels.len() * size_of::<Voxel>() => {
+                runs.shrink_to_fit();
+                Self::Rle(runs)
+            }
+            _ => Self::Dense(voxels),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Uniform { len, .. } => *len,
+            Self::Rle(runs) => runs.last().map(|&(end, _)| end as usize).unwrap_or(0),
+            Self::Dense(voxels) => voxels.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, index: usize) -> Voxel {
+        match self {
+            Self::Uniform { voxel, .. } => *voxel,
+            Self::Rle(runs) => {
+                let run = runs.partition_point(|&(end, _)| end as usize <= index);
+                runs[run].1
+            }
+            Self::Dense(voxels) => voxels[index],
+        }
+    }
+
+    /// Set voxel at "index", compressed voxels are unpacked, see [`VoxelStorage::compress`]
+    pub fn set(&mut self, index: usize, voxel: Voxel) {
+        if let Self::Uniform { voxel: uniform, .. } = self {
+            if is_same(uniform, &voxel) {
+                return;
+            }
+        }
+
+        if !matches!(self, Self::Dense(_)) {
+            *self = Self::Dense(self.to_vec());
+        }
+        if let Self::Dense(voxels) = self {
+            voxels[index] = voxel;
+        }
+    }
+
+    /// Pack voxels again after they were modified
+    pub fn compress(&mut self) {
+        if let Self::Dense(voxels) = self {
+            *self = Self::new(std::mem::take(voxels));
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<Voxel> {
+        match self {
+            Self::Uniform { voxel, len } => vec![*voxel; *len],
+            Self::Rle(runs) => {
+                let mut voxels = Vec::with_capacity(self.len());
+                for &(end, voxel) in runs.iter() {
+                    voxels.resize(end as usize, voxel);
+                }
+                voxels
+            }
+            Self::Dense(voxels) => voxels.clone(),
+        }
+    }
+
+    /// Min and max density of all voxels
+    pub fn get_value_range(&self) -> (f32, f32) {
+        let fold =
+            |(min, max): (f32, f32), voxel: &Voxel| (min.min(voxel.value), max.max(voxel.value));
+        let init = (f32::INFINITY, f32::NEG_INFINITY);
+
+        match self {
+            Self::Uniform { voxel, .. } => (voxel.value, voxel.value),
+            Self::Rle(runs) => runs.iter().map(|(_, voxel)| voxel).fold(init, fold),
+            Self::Dense(voxels) => voxels.iter().fold(init, fold),
+        }
+    }
+
+    /// Approximate size of voxels in memory in bytes
+    pub fn get_memory_size(&self) -> usize {
+        size_of::<Self>()
+            + match self {
+                Self::Uniform { .. } => 0,
+                Self::Rle(runs) => runs.capacity() * size_of::<(u32, Voxel)>(),
+                Self::Dense(voxels) => voxels.capacity() * size_of::<Voxel>(),
+            }
+    }
+}
+
+/// Bitwise equality, so runs are never merged across different voxels
+fn is_same(a: &Voxel, b: &Voxel) -> bool {
+    a.value.to_bits() == b.value.to_bits() && a.material == b.material
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn voxel(value: f32) -> Voxel {
+        Voxel { value, material: 0 }
+    }
+
+    /// 3 long runs: 0..100, 100..200 and 200..300
+    fn get_runs() -> Vec<Voxel> {
+        (0..300).map(|index| voxel((index / 100) as f32)).collect()
+    }
+
+    #[test]
+    fn picks_representation() {
+        assert!(matches!(
+            VoxelStorage::new(vec![voxel(1.); 100]),
+            VoxelStorage::Uniform { len: 100, .. }
+        ));
+        assert!(matches!(
+            VoxelStorage::new(Vec::new()),
+            VoxelStorage::Uniform { len: 0, .. }
+        ));
+        assert!(matches!(
+            VoxelStorage::new(get_runs()),
+            VoxelStorage::Rle(runs) if runs.len() == 3
+        ));
+
+        let noise = (0..300).map(|index| voxel(index as f32)).collect();
+        assert!(matches!(VoxelStorage::new(noise), VoxelStorage::Dense(_)));
+
+        // same density with different material is a different voxel
+        let mut voxels = vec![voxel(1.); 100];
+        voxels[50].material = 1;
+        assert!(!matches!(
+            VoxelStorage::new(voxels),
+            VoxelStorage::Uniform { .. }
+        ));
+    }
+
+    #[test]
+    fn get_at_run_boundaries() {
+        let storage = VoxelStorage::new(get_runs());
+        assert_eq!(storage.len(), 300);
+
+        for (index, value) in [
+            (0, 0.),
+            (99, 0.),
+            (100, 1.),
+            (199, 1.),
+            (200, 2.),
+            (299, 2.),
+        ] {
+            assert_eq!(storage.get(index).value, value, "voxel {}", index);
+        }
+    }
+
+    #[test]
+    fn set_unpacks_uniform() {
+        let mut storage = VoxelStorage::new(vec![voxel(1.); 100]);
+
+        // same voxel keeps it uniform
+        storage.set(10, voxel(1.));
+        assert!(matches!(storage, VoxelStorage::Uniform { .. }));
+
+        storage.set(10, voxel(2.));
+        assert!(matches!(storage, VoxelStorage::Dense(_)));
+        assert_eq!(storage.len(), 100);
+        assert_eq!(storage.get(9).value, 1.);
+        assert_eq!(storage.get(10).value, 2.);
+        assert_eq!(storage.get(11).value, 1.);
+    }
+
+    #[test]
+    fn set_unpacks_rle() {
+        let mut storage = VoxelStorage::new(get_runs());
+        storage.set(100, voxel(5.));
+
+        let voxels = storage.to_vec();
+        assert_eq!(voxels.len(), 300);
+        for (index, voxel) in voxels.iter().enumerate() {
+            let expected = if index == 100 {
+                5.
+            } else {
+                (index / 100) as f32
+            };
+            assert_eq!(voxel.value, expected, "voxel {}", index);
+        }
+
+        // compression splits the changed run
+        storage.compress();
+        assert!(matches!(&storage, VoxelStorage::Rle(runs) if runs.len() == 4));
+        assert_eq!(storage.to_vec().len(), 300);
+    }
+}
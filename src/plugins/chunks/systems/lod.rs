@@ -0,0 +1,60 @@
+use crate::plugins::chunks::{
+    components::ChunkLoader,
+    resources::{chunk::CHUNK_REAL_SIZE, lod::LodSettings, pos::Position, ChunksHolder},
+};
+use bevy::prelude::*;
+
+/// Update level of detail of chunks by distance to the nearest chunk loader
+pub fn update_lod_sys(
+    settings: Res<LodSettings>,
+    mut chunks: ResMut<ChunksHolder>,
+    loaders: Query<&GlobalTransform, With<ChunkLoader>>,
+) {
+    let loaders: Vec<Position> = loaders
+        .iter()
+        .map(|transform| {
+            Position::from_vec(transform.translation).div_floor(CHUNK_REAL_SIZE as i64)
+        })
+        .collect();
+
+    if loaders.is_empty() {
+        return;
+    }
+
+    let changed: Vec<(Position, u8)> = chunks
+        .iter()
+        .filter_map(|chunk| {
+            let pos = chunk.get_pos();
+            let distance_squared = loaders
+                .iter()
+                .map(|&loader_pos| (pos - loader_pos).length_squared())
+                .min()
+                .unwrap_or(0);
+            let lod = settings.get_lod((distance_squared as f64).sqrt() as i64);
+
+            (lod != chunk.get_lod()).then_some((pos, lod))
+        })
+        .collect();
+
+    let faces = [
+        Position::new(1, 0, 0),
+        Position::new(-1, 0, 0),
+        Position::new(0, 1, 0),
+        Position::new(0, -1, 0),
+        Position::new(0, 0, 1),
+        Position::new(0, 0, -1),
+    ];
+
+    for (pos, lod) in changed {
+        if let Some(chunk) = chunks.get_mut(pos) {
+            chunk.set_lod(lod);
+        }
+
+        // neighbours need to add or remove skirts at the shared face
+        for offset in faces {
+            if let Some(chunk) = chunks.get_mut(pos + offset) {
+                chunk.set_need_update();
+            }
+        }
+    }
+}
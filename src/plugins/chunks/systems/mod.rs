@@ -1,12 +1,48 @@
-use super::resources::ChunksHolder;
-use bevy::prelude::*;
+use super::resources::{
+    generator::ChunksGenerator, region::RegionStorage, tasks::ChunkTasks, ChunksHolder,
+};
+use bevy::{app::AppExit, prelude::*, tasks::ComputeTaskPool};
+use futures_lite::future;
 
+pub mod lod;
+pub mod octree;
 pub mod redraw_chunk;
+pub mod stream_chunks;
 
 const BASE_WORLD_SIZE: usize = 8;
 
-pub fn chunks_startup_sys(mut commands: Commands) {
-    let chunks = ChunksHolder::new(BASE_WORLD_SIZE);
+pub fn chunks_startup_sys(
+    mut commands: Commands,
+    generator: Res<ChunksGenerator>,
+    pool: Res<ComputeTaskPool>,
+    storage: Option<Res<RegionStorage>>,
+) {
+    let mut chunks = ChunksHolder::new(BASE_WORLD_SIZE, generator.0.clone(), &pool);
+    if let Some(storage) = storage {
+        chunks = chunks.with_storage(storage.clone());
+    }
 
     commands.insert_resource(chunks);
 }
+
+/// Save chunks with unsaved changes before the app exits
+pub fn save_chunks_on_exit_sys(
+    mut exit: EventReader<AppExit>,
+    mut chunks: ResMut<ChunksHolder>,
+    mut tasks: ResMut<ChunkTasks>,
+) {
+    if exit.iter().next().is_none() {
+        return;
+    }
+
+    // unloaded chunks left in the queue are written below
+    if let Some(task) = tasks.saving.take() {
+        if let Err(err) = future::block_on(task) {
+            error!("failed to save unloaded chunks: {}", err);
+        }
+    }
+
+    if let Err(err) = chunks.save() {
+        error!("failed to save chunks: {}", err);
+    }
+}
@@ -0,0 +1,207 @@
+use crate::plugins::chunks::{
+    components::{ChunkComponent, ChunkLoader},
+    resources::{
+        generator::ChunksGenerator,
+        material::ChunkMaterial,
+        octree::{ChunkOctree, OctreeSettings},
+        pos::Position,
+        tasks::{OctreeTasks, TasksSettings},
+    },
+};
+use bevy::{
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, ComputeTaskPool},
+};
+use futures_lite::future;
+
+pub fn octree_startup_sys(
+    mut commands: Commands,
+    settings: Res<OctreeSettings>,
+    generator: Res<ChunksGenerator>,
+    pool: Res<ComputeTaskPool>,
+) {
+    let octree = ChunkOctree::new(settings.max_level, generator.0.clone(), &pool);
+
+    commands.insert_resource(octree);
+}
+
+/// Split and merge octree nodes around chunk loaders
+///
+/// Chunks of new nodes are generated in background, entities of removed leaves are retired
+/// instead of despawned, so there are no holes until new leaves are meshed.
+pub fn update_octree_sys(
+    settings: Res<OctreeSettings>,
+    pool: Res<AsyncComputeTaskPool>,
+    mut octree: ResMut<ChunkOctree>,
+    mut tasks: ResMut<OctreeTasks>,
+    loaders: Query<&GlobalTransform, With<ChunkLoader>>,
+) {
+    let loaders: Vec<Vec3> = loaders
+        .iter()
+        .map(|transform| transform.translation)
+        .collect();
+    let updates = octree.get_updates(&loaders, &settings);
+
+    // updates which aren't needed anymore are cancelled
+    tasks.updates.retain(|update, _| updates.contains(update));
+
+    let mut generated = Vec::new();
+    tasks
+        .updates
+        .retain(|_, task| match future::block_on(future::poll_once(task)) {
+            Some(update) => {
+                generated.push(update);
+                false
+            }
+            None => true,
+        });
+    for update in generated {
+        for leaf in octree.apply_update(update) {
+            tasks.meshing.remove(&(leaf.get_level(), leaf.get_pos()));
+            if let Some(entity) = leaf.get_chunk().and_then(|chunk| chunk.get_entity()) {
+                tasks.retired.push((entity, leaf.get_aabb()));
+            }
+        }
+    }
+
+    for update in updates {
+        if tasks.updates.len() >= settings.update_budget {
+            break;
+        }
+        if tasks.updates.contains_key(&update) {
+            continue;
+        }
+
+        let job = octree.get_update_job(update);
+        tasks
+            .updates
+            .insert(update, pool.spawn(async move { job.generate() }));
+    }
+}
+
+/// Start meshing octree leaves which need redraw
+pub fn redraw_octree_sys(
+    mut commands: Commands,
+    settings: Res<TasksSettings>,
+    pool: Res<AsyncComputeTaskPool>,
+    mut octree: ResMut<ChunkOctree>,
+    mut tasks: ResMut<OctreeTasks>,
+) {
+    let iso_level = octree.get_mesh_settings().iso_level;
+    let leaves: Vec<(u8, Position)> = octree
+        .leaves()
+        .into_iter()
+        .filter(|leaf| leaf.get_chunk().is_some_and(|chunk| chunk.is_need_update()))
+        .map(|leaf| (leaf.get_level(), leaf.get_pos()))
+        .collect();
+
+    for (level, pos) in leaves {
+        // leaf was marked again while being meshed, replace the stale job
+        let is_stale = tasks.meshing.remove(&(level, pos)).is_some();
+
+        // leaves without surface are not meshed and have no entity
+        if let Some(chunk) = octree
+            .get_node_mut(level, pos)
+            .and_then(|node| node.get_chunk_mut())
+            .filter(|chunk| chunk.is_uniform(iso_level))
+        {
+            if let Some(entity) = chunk.get_entity() {
+                commands.entity(entity).despawn();
+                chunk.set_entity(None);
+            }
+            chunk.set_updated();
+            continue;
+        }
+
+        if !is_stale && tasks.meshing.len() >= settings.max_meshing_tasks {
+            continue;
+        }
+
+        let job = match octree.get_mesh_job(level, pos) {
+            Some(job) => job,
+            None => continue,
+        };
+        let task = pool.spawn(async move { job.generate_mesh() });
+        tasks.meshing.insert((level, pos), task);
+
+        if let Some(chunk) = octree
+            .get_node_mut(level, pos)
+            .and_then(|node| node.get_chunk_mut())
+        {
+            chunk.set_updated();
+        }
+    }
+}
+
+/// Replace meshes of leaves which finished meshing and despawn retired entities once leaves
+/// covering them are meshed
+pub fn apply_octree_meshes_sys(
+    mut commands: Commands,
+    mut octree: ResMut<ChunkOctree>,
+    mut tasks: ResMut<OctreeTasks>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    material: Res<ChunkMaterial>,
+    chunk_meshes: Query<&Handle<Mesh>, With<ChunkComponent>>,
+) {
+    tasks.meshing.retain(|&(level, pos), task| {
+        let mesh = match future::block_on(future::poll_once(task)) {
+            Some(mesh) => mesh,
+            None => return true,
+        };
+
+        let node = match octree
+            .get_node_mut(level, pos)
+            .filter(|node| node.get_level() == level)
+        {
+            Some(node) => node,
+            None => return false,
+        };
+        let scale = node.get_scale() as f32;
+        let chunk = match node.get_chunk_mut() {
+            Some(chunk) => chunk,
+            None => return false,
+        };
+
+        // replace mesh of already spawned leaf entity
+        let handle = chunk
+            .get_entity()
+            .and_then(|entity| chunk_meshes.get(entity).ok());
+        match handle.and_then(|handle| meshes.get_mut(handle)) {
+            Some(old_mesh) => *old_mesh = mesh,
+            None => {
+                // leaf meshes are built in units of the leaf's scale
+                let entity = commands
+                    .spawn_bundle(MaterialMeshBundle {
+                        mesh: meshes.add(mesh),
+                        material: material.0.clone(),
+                        transform: Transform::from_scale(Vec3::splat(scale)),
+                        ..default()
+                    })
+                    .insert(ChunkComponent::new(pos))
+                    .id();
+                chunk.set_entity(Some(entity));
+            }
+        }
+
+        false
+    });
+
+    let OctreeTasks {
+        meshing, retired, ..
+    } = &mut *tasks;
+    retired.retain(|(entity, aabb)| {
+        // shrink bounds so leaves which only touch them are not waited for
+        let min = Vec3::from(aabb.min()) + 0.5;
+        let max = Vec3::from(aabb.max()) - 0.5;
+        let is_covered = octree.query_aabb(min, max).into_iter().all(|leaf| {
+            leaf.get_chunk()
+                .is_some_and(|chunk| !chunk.is_need_update())
+                && !meshing.contains_key(&(leaf.get_level(), leaf.get_pos()))
+        });
+
+        if is_covered {
+            commands.entity(*entity).despawn();
+        }
+        !is_covered
+    });
+}
@@ -1,32 +1,209 @@
-use crate::plugins::chunks::resources::ChunksHolder;
-use bevy::prelude::*;
+use crate::plugins::chunks::{
+    components::ChunkComponent,
+    resources::{
+        chunk_view::ChunkView,
+        material::ChunkMaterial,
+        pos::Position,
+        tasks::{ChunkMeshes, ChunkTasks, TasksSettings},
+        ChunksHolder,
+    },
+};
+use bevy::{prelude::*, tasks::AsyncComputeTaskPool};
+use futures_lite::future;
 
+/// Start meshing chunks which need redraw
 pub fn redraw_chunk_sys(
     mut commands: Commands,
+    settings: Res<TasksSettings>,
+    pool: Res<AsyncComputeTaskPool>,
     mut chunks: ResMut<ChunksHolder>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut tasks: ResMut<ChunkTasks>,
 ) {
+    let mesh_settings = *chunks.get_mesh_settings();
+    let generator = chunks.get_generator().clone();
+
+    let positions: Vec<Position> = chunks
+        .iter()
+        .filter(|chunk| chunk.is_need_update())
+        .map(|chunk| chunk.get_pos())
+        .collect();
+
     // iterate through all chunks and redraw if necessary
-    chunks.iter_chunks_mut().for_each(|chunk| match chunk {
-        Some(chunk) if chunk.is_need_update() => {
-            let mesh = chunk.generate_mesh();
-
-            commands.spawn_bundle(PbrBundle {
-                mesh: meshes.add(mesh),
-                material: materials.add(StandardMaterial {
-                    base_color: Color::rgb(1.0, 1.0, 1.0).into(),
-                    perceptual_roughness: 1.,
-                    metallic: 0.,
-                    reflectance: 0.,
-                    ..default()
-                }),
-                ..default()
-            });
-
-            // set state to updated after redraw completes
+    for pos in positions {
+        // chunk was modified while being meshed, replace the stale job
+        let is_stale = tasks.meshing.remove(&pos).is_some();
+
+        // pack voxels modified since the last redraw, which also shrinks their value range
+        // widened by edits, before checking if the chunk has surface and sharing it with the task
+        if let Some(chunk) = chunks.get_mut(pos) {
+            chunk.compress();
+        }
+
+        // chunks without surface are not meshed and have no entity
+        if let Some(chunk) = chunks
+            .get_mut(pos)
+            .filter(|chunk| chunk.is_uniform(mesh_settings.iso_level))
+        {
+            if let Some(entity) = chunk.get_entity() {
+                commands.entity(entity).despawn();
+                chunk.set_entity(None);
+            }
+            chunk.set_updated();
+            continue;
+        }
+
+        if !is_stale && tasks.meshing.len() >= settings.max_meshing_tasks {
+            continue;
+        }
+
+        let neighbours = chunks.get_neighbours(pos);
+        let generator = generator.clone();
+        let task = pool.spawn(async move {
+            let data =
+                ChunkView::new(&neighbours, generator.as_ref()).generate_vertices(&mesh_settings);
+            ChunkMeshes::new(data)
+        });
+        tasks.meshing.insert(pos, task);
+
+        // set state to updated, chunk will be marked again if it's modified before redraw completes
+        if let Some(chunk) = chunks.get_mut(pos) {
             chunk.set_updated();
         }
-        _ => {}
+    }
+}
+
+/// Replace meshes of chunks which finished meshing
+pub fn apply_chunk_meshes_sys(
+    mut commands: Commands,
+    mut chunks: ResMut<ChunksHolder>,
+    mut tasks: ResMut<ChunkTasks>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    material: Res<ChunkMaterial>,
+    chunk_meshes: Query<&Handle<Mesh>, With<ChunkComponent>>,
+) {
+    tasks.meshing.retain(|&pos, task| {
+        let result = match future::block_on(future::poll_once(task)) {
+            Some(result) => result,
+            None => return true,
+        };
+
+        let chunk = match chunks.get_mut(pos) {
+            Some(chunk) => chunk,
+            None => return false,
+        };
+
+        // replace mesh of already spawned chunk entity
+        let handle = chunk
+            .get_entity()
+            .and_then(|entity| chunk_meshes.get(entity).ok());
+        match handle.and_then(|handle| meshes.get_mut(handle)) {
+            Some(old_mesh) => *old_mesh = result.mesh,
+            None => {
+                let entity = commands
+                    .spawn_bundle(MaterialMeshBundle {
+                        mesh: meshes.add(result.mesh),
+                        material: material.0.clone(),
+                        ..default()
+                    })
+                    .insert(ChunkComponent::new(pos))
+                    .id();
+                chunk.set_entity(Some(entity));
+            }
+        }
+
+        // collider is replaced together with the mesh
+        #[cfg(feature = "rapier")]
+        if let Some(entity) = chunk.get_entity() {
+            use bevy_rapier3d::prelude::Collider;
+
+            match result.collider {
+                Some(collider) => commands.entity(entity).insert(collider),
+                None => commands.entity(entity).remove::<Collider>(),
+            };
+        }
+
+        false
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::chunks::resources::{
+        generator::DensityGenerator, voxel::Voxel, ChunksHolder,
+    };
+    use bevy::tasks::TaskPool;
+    use std::sync::Arc;
+
+    /// Empty space
+    struct Air;
+
+    impl DensityGenerator for Air {
+        fn get_voxel(&self, _pos: Position) -> Voxel {
+            Voxel {
+                value: -1.,
+                material: 0,
+            }
+        }
+    }
+
+    fn run_redraw(world: &mut World) {
+        SystemStage::single(redraw_chunk_sys).run(world);
+    }
+
+    #[test]
+    fn uniform_chunks_are_not_meshed() {
+        let pool = TaskPool::new();
+        let mut world = World::new();
+        world.insert_resource(ChunksHolder::new(1, Arc::new(Air), &pool));
+        world.insert_resource(ChunkTasks::default());
+        world.insert_resource(TasksSettings::default());
+        world.insert_resource(AsyncComputeTaskPool(pool));
+
+        // mesh entity left from the time the chunk had surface
+        let pos = Position::new(0, 0, 0);
+        let old_entity = world.spawn().id();
+        world
+            .resource_mut::<ChunksHolder>()
+            .get_mut(pos)
+            .unwrap()
+            .set_entity(Some(old_entity));
+
+        run_redraw(&mut world);
+        let chunk = world.resource::<ChunksHolder>().get(pos).unwrap();
+        assert!(!chunk.is_need_update());
+        assert!(chunk.get_entity().is_none());
+        assert!(world.get_entity(old_entity).is_none());
+        assert!(world.resource::<ChunkTasks>().meshing.is_empty());
+
+        // filled voxel crosses the iso level
+        let voxel = Position::new(5, 5, 5);
+        let filled = Voxel {
+            value: 1.,
+            material: 0,
+        };
+        world
+            .resource_mut::<ChunksHolder>()
+            .set_voxel_world(voxel, filled);
+        run_redraw(&mut world);
+        let task = world
+            .resource_mut::<ChunkTasks>()
+            .meshing
+            .remove(&pos)
+            .expect("chunk with surface is meshed");
+        assert!(future::block_on(task).mesh.count_vertices() > 0);
+
+        // value range widened by the edit shrinks back once the voxel is empty again
+        let empty = Air.get_voxel(voxel);
+        world
+            .resource_mut::<ChunksHolder>()
+            .set_voxel_world(voxel, empty);
+        run_redraw(&mut world);
+        assert!(world.resource::<ChunkTasks>().meshing.is_empty());
+        assert!(!world
+            .resource::<ChunksHolder>()
+            .get(pos)
+            .unwrap()
+            .is_need_update());
+    }
+}
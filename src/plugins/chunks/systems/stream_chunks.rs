@@ -0,0 +1,148 @@
+use crate::plugins::chunks::{
+    components::ChunkLoader,
+    resources::{
+        chunk::{Chunk, CHUNK_REAL_SIZE},
+        pos::Position,
+        streaming::StreamingSettings,
+        tasks::{ChunkTasks, TasksSettings},
+        ChunksHolder,
+    },
+};
+use bevy::{prelude::*, tasks::AsyncComputeTaskPool, utils::HashSet};
+use futures_lite::future;
+
+/// Start generating chunks around chunk loaders and unload chunks which are too far from all of them
+pub fn stream_chunks_sys(
+    mut commands: Commands,
+    settings: Res<StreamingSettings>,
+    tasks_settings: Res<TasksSettings>,
+    pool: Res<AsyncComputeTaskPool>,
+    mut chunks: ResMut<ChunksHolder>,
+    mut tasks: ResMut<ChunkTasks>,
+    loaders: Query<(&ChunkLoader, &GlobalTransform)>,
+) {
+    // chunk position and radius of each loader
+    let loaders: Vec<(Position, i64)> = loaders
+        .iter()
+        .map(|(loader, transform)| {
+            let pos = Position::from_vec(transform.translation).div_floor(CHUNK_REAL_SIZE as i64);
+            (pos, loader.radius)
+        })
+        .collect();
+
+    if loaders.is_empty() {
+        return;
+    }
+
+    let is_needed = |pos: Position| {
+        loaders.iter().any(|&(loader_pos, radius)| {
+            let radius = radius + settings.unload_margin;
+            (pos - loader_pos).length_squared() <= radius * radius
+        })
+    };
+
+    // chunks which are not generated yet are dropped immediately
+    tasks.generation.retain(|&pos, _| is_needed(pos));
+
+    // unload the farthest chunks first
+    let mut to_unload: Vec<(i64, Position)> = chunks
+        .positions()
+        .filter(|&pos| !is_needed(pos))
+        .map(|pos| (distance_to_loaders(&loaders, pos), pos))
+        .collect();
+    to_unload.sort_unstable_by(|a, b| b.cmp(a));
+    to_unload.truncate(settings.unload_budget);
+
+    let mut unloaded: Vec<Chunk> = Vec::new();
+    for (_, pos) in to_unload {
+        tasks.cancel(pos);
+        if let Some(chunk) = chunks.remove(pos) {
+            if let Some(entity) = chunk.get_entity() {
+                commands.entity(entity).despawn();
+            }
+            unloaded.push(chunk);
+        }
+    }
+
+    // changes would be lost otherwise, they are written in batches in the background
+    if let Some(storage) = chunks.get_storage() {
+        storage.queue(unloaded);
+
+        if let Some(task) = tasks.saving.as_mut() {
+            if let Some(result) = future::block_on(future::poll_once(task)) {
+                if let Err(err) = result {
+                    error!("failed to save unloaded chunks: {}", err);
+                }
+                tasks.saving = None;
+            }
+        }
+        if tasks.saving.is_none() && storage.has_unsaved() {
+            let storage = storage.clone();
+            tasks.saving = Some(pool.spawn(async move { storage.flush() }));
+        }
+    }
+
+    // load the nearest missing chunks first
+    let mut to_load: Vec<(i64, Position)> = Vec::new();
+    let mut visited: HashSet<Position> = HashSet::default();
+    for &(loader_pos, radius) in loaders.iter() {
+        for x in -radius..=radius {
+            for y in -radius..=radius {
+                for z in -radius..=radius {
+                    let offset = Position::new(x, y, z);
+                    let pos = loader_pos + offset;
+                    if offset.length_squared() > radius * radius
+                        || chunks.contains(pos)
+                        || tasks.generation.contains_key(&pos)
+                        || !visited.insert(pos)
+                    {
+                        continue;
+                    }
+                    to_load.push((distance_to_loaders(&loaders, pos), pos));
+                }
+            }
+        }
+    }
+    to_load.sort_unstable();
+
+    let free_slots = tasks_settings
+        .max_generation_tasks
+        .saturating_sub(tasks.generation.len());
+    for (_, pos) in to_load
+        .into_iter()
+        .take(settings.load_budget.min(free_slots))
+    {
+        let generator = chunks.get_generator().clone();
+        let storage = chunks.get_storage().cloned();
+        let task = pool.spawn(async move {
+            match storage {
+                Some(storage) => storage.load_or_generate(pos, generator.as_ref()),
+                None => Chunk::new(pos, generator.as_ref()),
+            }
+        });
+        tasks.generation.insert(pos, task);
+    }
+}
+
+/// Add chunks which finished generation to the world
+pub fn apply_generated_chunks_sys(mut chunks: ResMut<ChunksHolder>, mut tasks: ResMut<ChunkTasks>) {
+    tasks
+        .generation
+        .retain(|_, task| match future::block_on(future::poll_once(task)) {
+            Some(chunk) => {
+                // neighbours are marked for redraw
+                chunks.insert(chunk);
+                false
+            }
+            None => true,
+        });
+}
+
+/// Squared distance (in chunks) to the nearest loader
+fn distance_to_loaders(loaders: &[(Position, i64)], pos: Position) -> i64 {
+    loaders
+        .iter()
+        .map(|&(loader_pos, _)| (pos - loader_pos).length_squared())
+        .min()
+        .unwrap_or(0)
+}